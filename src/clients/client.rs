@@ -1,32 +1,77 @@
-use anyhow::{Result, anyhow};
-use num::{CheckedAdd, CheckedSub, Signed, Zero};
+use num::{CheckedAdd, CheckedSub, Signed};
+use std::collections::{BTreeMap, HashMap};
 
 use crate::transactions::TransactionType::*;
-use crate::transactions::transaction::{Chargeback, ClaimType, Dispute, Resolve};
-use crate::util::merge_in_place;
-use crate::{
-    transactions::{Transaction, UnprocessedTransaction},
-    util::Fixed,
+use crate::transactions::transaction::ClaimType;
+use crate::transactions::{
+    Amount, Asset, DisputeState, FundsType, Transaction, TransactionError, UnprocessedTransaction,
 };
+use crate::util::merge_in_place;
 
-#[derive(Default, Debug, Clone, Copy)]
+// the balance of a single asset
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub struct ClientState {
-    available_funds: Fixed,
-    held_funds: Fixed,
-    locked: bool,
+    available_funds: Amount,
+    held_funds: Amount,
+}
+
+impl ClientState {
+    pub(crate) fn new(available_funds: Amount, held_funds: Amount) -> Self {
+        Self {
+            available_funds,
+            held_funds,
+        }
+    }
+
+    pub fn available_funds(&self) -> Amount {
+        self.available_funds
+    }
+
+    pub fn held_funds(&self) -> Amount {
+        self.held_funds
+    }
+
+    pub fn total_funds(&self) -> Amount {
+        self.available_funds + self.held_funds
+    }
+}
+
+// behaviour which can be configured for every client
+#[derive(Debug, Clone, Copy)]
+pub struct ClientConfig {
+    // whether a resolved transaction can be disputed again
+    pub allow_redispute: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            allow_redispute: true,
+        }
+    }
+}
+
+// a copy of a client's balances, without the transaction history needed to handle claims
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientSnapshot {
+    pub id: u64,
+    pub states: BTreeMap<Asset, ClientState>,
+    pub locked: bool,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct Client {
     id: u64,
-    state: ClientState,
-    // what chronology was the account locked at
+    // kept up to date as each transaction is handled, ordered by asset for output
+    states: BTreeMap<Asset, ClientState>,
+    // what chronology was the account locked at, locking applies to every asset
     locked: Option<u64>,
-    sorted_transactions: Vec<Transaction>,
-    unsorted_transactions: Vec<Transaction>,
-    disputes: Vec<Dispute>,
-    resolves: Vec<Resolve>,
-    chargebacks: Vec<Chargeback>,
+    // accepted deposits and withdrawals along with their dispute state, sorted by (chronology, id)
+    transactions: Vec<Transaction>,
+    // transaction id -> chronology, enough to binary search transactions by (chronology, id)
+    // positions in transactions shift as out of order transactions are merged in,
+    // but chronologies don't, so the index stays valid without being rebuilt
+    transaction_index: HashMap<u64, u64>,
 }
 
 impl Client {
@@ -37,189 +82,468 @@ impl Client {
         }
     }
 
-    pub fn handle_transaction(&mut self, transaction: UnprocessedTransaction) -> Result<()> {
+    pub fn handle_transaction(
+        &mut self,
+        transaction: UnprocessedTransaction,
+        config: &ClientConfig,
+    ) -> Result<(), TransactionError> {
         // TODO combine id and chronology and implement Ord, Cmp
         let id = transaction.metadata.transaction_id;
         let chronology = transaction.metadata.chronology;
+        let asset = transaction.metadata.asset;
 
         // if we are locked, don't process any future transactions
+        // a transaction at the same chronology as the chargeback is being applied after it, so is in the future too,
+        // which also covers callers who leave every chronology at 0
         if let Some(locked_chronology) = self.locked
-            && (locked_chronology, id) < (chronology, id)
+            && chronology >= locked_chronology
         {
-            // this is not a system error - in a more robust system I would return an error to the caller here
-            return Ok(());
+            return Err(TransactionError::AccountLocked);
         }
 
         match transaction.transaction_type {
-            UpdateFunds(amount) => self.handle_update_funds(id, chronology, amount),
-            Claim(claim_type) => self.handle_claim(id, chronology, claim_type),
+            Deposit(amount) => {
+                self.handle_update_funds(id, chronology, FundsType::Deposit, asset, amount)
+            }
+            Withdrawal(amount) => {
+                self.handle_update_funds(id, chronology, FundsType::Withdrawal, asset, amount)
+            }
+            Claim(claim_type) => self.handle_claim(id, chronology, claim_type, config),
+        }
+    }
+
+    fn handle_update_funds(
+        &mut self,
+        id: u64,
+        chronology: u64,
+        funds_type: FundsType,
+        asset: Asset,
+        amount: Amount,
+    ) -> Result<(), TransactionError> {
+        if self.transaction_index.contains_key(&id) {
+            return Err(TransactionError::DuplicateTransactionId);
+        }
+
+        let state = self.state(asset);
+        let available_funds = match funds_type {
+            FundsType::Deposit => state
+                .available_funds
+                .checked_add(&amount)
+                .ok_or(TransactionError::Overflow)?,
+            FundsType::Withdrawal => {
+                let available_funds = state
+                    .available_funds
+                    .checked_sub(&amount)
+                    .ok_or(TransactionError::Overflow)?;
+
+                // available_funds only allowed to be 0 or above
+                // a rejected withdrawal never happened, so it is not kept for later disputes
+                if available_funds.is_negative() {
+                    return Err(TransactionError::InsufficientFunds);
+                }
+                available_funds
+            }
         };
 
+        self.states.entry(asset).or_default().available_funds = available_funds;
+
+        // keep the transaction in case of a later dispute
+        self.insert_transaction(Transaction::new(id, chronology, funds_type, asset, amount));
+
         Ok(())
     }
 
-    fn handle_update_funds(&mut self, id: u64, chronology: u64, amount: Fixed) {
-        // add transaction in case of either:
-        //  1. later dispute
-        //  2. this transaction being an insufficient funds withdrawal and later processing an earlier deposit giving enough funds for withdrawal
-        self.unsorted_transactions
-            .push(Transaction::new(id, chronology, amount));
+    fn insert_transaction(&mut self, transaction: Transaction) {
+        self.transaction_index
+            .insert(transaction.id, transaction.chronology);
+
+        // transactions almost always arrive in order, making this an O(1) push
+        match self.transactions.last() {
+            Some(last) if *last > transaction => {
+                merge_in_place(&mut self.transactions, &[transaction]);
+            }
+            _ => self.transactions.push(transaction),
+        }
     }
 
-    fn handle_claim(&mut self, id: u64, chronology: u64, claim_type: ClaimType) {
-        match claim_type {
-            ClaimType::Dispute => {
-                self.disputes.push(Dispute { id, chronology });
+    fn handle_claim(
+        &mut self,
+        id: u64,
+        chronology: u64,
+        claim_type: ClaimType,
+        config: &ClientConfig,
+    ) -> Result<(), TransactionError> {
+        let transaction_index = self
+            .find_transaction_by_id(id)
+            .ok_or(TransactionError::UnknownTransaction)?;
+        let transaction = self.transactions[transaction_index];
+
+        // a claim can't reference a transaction which hadn't happened yet
+        if chronology < transaction.chronology {
+            return Err(TransactionError::UnknownTransaction);
+        }
+
+        let dispute_state = match (claim_type, transaction.dispute_state) {
+            (ClaimType::Dispute, DisputeState::Normal) => {
+                self.states
+                    .insert(transaction.asset, self.hold_funds(&transaction)?);
+                DisputeState::Disputed(chronology)
+            }
+            (ClaimType::Dispute, DisputeState::Resolved(_)) if config.allow_redispute => {
+                self.states
+                    .insert(transaction.asset, self.hold_funds(&transaction)?);
+                DisputeState::Disputed(chronology)
+            }
+            (ClaimType::Dispute, DisputeState::Disputed(_)) => {
+                return Err(TransactionError::AlreadyDisputed);
+            }
+            (ClaimType::Dispute, DisputeState::Resolved(_) | DisputeState::ChargedBack(_)) => {
+                return Err(TransactionError::DisputeSettled);
             }
-            ClaimType::Resolve => {
-                self.resolves.push(Resolve { id, chronology });
+            // a resolve or chargeback must come after the dispute it settles
+            (ClaimType::Resolve, DisputeState::Disputed(disputed_chronology))
+                if chronology >= disputed_chronology =>
+            {
+                self.states
+                    .insert(transaction.asset, self.release_funds(&transaction)?);
+                DisputeState::Resolved(chronology)
             }
-            ClaimType::Chargeback => {
-                self.chargebacks.push(Chargeback { id, chronology });
+            (ClaimType::Chargeback, DisputeState::Disputed(disputed_chronology))
+                if chronology >= disputed_chronology =>
+            {
+                self.states
+                    .insert(transaction.asset, self.charge_back_funds(&transaction)?);
+                // lock
+                self.locked = Some(chronology);
+                DisputeState::ChargedBack(chronology)
             }
+            (ClaimType::Resolve | ClaimType::Chargeback, _) => {
+                return Err(TransactionError::NotDisputed);
+            }
+        };
+
+        self.transactions[transaction_index].dispute_state = dispute_state;
+
+        Ok(())
+    }
+
+    // the following calculate the new state of the transaction's asset without modifying the client,
+    // so that a transaction which overflows leaves the client untouched
+
+    // moves the disputed amount into held funds
+    fn hold_funds(&self, transaction: &Transaction) -> Result<ClientState, TransactionError> {
+        let mut state = self.state(transaction.asset);
+
+        // a disputed withdrawal isn't described in the brief so I am making assumptions
+        // the withdrawal has still happened so available funds are not returned prematurely
+        // held funds increase by the withdrawn amount so that if the transaction was indeed fraudulent,
+        //  a chargeback will return those funds to available funds
+        if transaction.funds_type == FundsType::Deposit {
+            // this is allowed to result in negative available funds
+            state.available_funds = state
+                .available_funds
+                .checked_sub(&transaction.amount)
+                .ok_or(TransactionError::Overflow)?;
         }
+
+        state.held_funds = state
+            .held_funds
+            .checked_add(&transaction.amount)
+            .ok_or(TransactionError::Overflow)?;
+
+        Ok(state)
     }
 
-    //
-    fn resolve_disputes(&mut self) {
-        for (dispute_index, dispute) in self.disputes.clone().iter().enumerate() {
-            // first check if transaction has been processed yet
-            if let Some(transaction_index) = self.find_transaction_by_id(dispute.id) {
-                // check if we have a matching resolution
-                if let Some(resolve_index) = self.find_resolve_by_id(dispute.id) {
-                    // just remove the dispute and resolve
-                    self.disputes.remove(dispute_index);
-                    self.resolves.remove(resolve_index);
-                } else if let Some(chargeback_index) = self.find_chargeback_by_id(dispute.id) {
-                    // lock
-                    self.locked = Some(self.chargebacks[chargeback_index].chronology);
-                    // remove the dispute and chargeback, and remove the transaction
-                    self.disputes.remove(dispute_index);
-                    self.chargebacks.remove(chargeback_index);
-                    self.sorted_transactions.remove(transaction_index);
-                }
-                // if we don't have a matching resolution
-                else {
-                    // mark the transaction as disputed
-                    self.sorted_transactions[transaction_index].disputed = true;
-                }
-            }
+    // undoes hold_funds
+    fn release_funds(&self, transaction: &Transaction) -> Result<ClientState, TransactionError> {
+        let mut state = self.state(transaction.asset);
+
+        state.held_funds = state
+            .held_funds
+            .checked_sub(&transaction.amount)
+            .ok_or(TransactionError::Overflow)?;
+
+        if transaction.funds_type == FundsType::Deposit {
+            state.available_funds = state
+                .available_funds
+                .checked_add(&transaction.amount)
+                .ok_or(TransactionError::Overflow)?;
         }
+
+        Ok(state)
     }
 
-    fn find_resolve_by_id(&mut self, id: u64) -> Option<usize> {
-        self.resolves
-            .iter_mut()
-            .enumerate()
-            .find(|(_, resolution)| resolution.id == id)
-            .map(|(index, _)| index)
+    // reverses the original transaction, using the funds held by the dispute
+    fn charge_back_funds(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<ClientState, TransactionError> {
+        let mut state = self.state(transaction.asset);
+
+        state.held_funds = state
+            .held_funds
+            .checked_sub(&transaction.amount)
+            .ok_or(TransactionError::Overflow)?;
+
+        if transaction.funds_type == FundsType::Withdrawal {
+            state.available_funds = state
+                .available_funds
+                .checked_add(&transaction.amount)
+                .ok_or(TransactionError::Overflow)?;
+        }
+
+        Ok(state)
     }
 
-    fn find_chargeback_by_id(&mut self, id: u64) -> Option<usize> {
-        self.chargebacks
-            .iter_mut()
-            .enumerate()
-            .find(|(_, resolution)| resolution.id == id)
-            .map(|(index, _)| index)
+    // O(1) lookup of the chronology, then O(log n) search of the sorted transactions
+    fn find_transaction_by_id(&self, id: u64) -> Option<usize> {
+        let key = (*self.transaction_index.get(&id)?, id);
+        self.transactions
+            .binary_search_by(|transaction| (transaction.chronology, transaction.id).cmp(&key))
+            .ok()
     }
 
-    fn find_transaction_by_id(&mut self, id: u64) -> Option<usize> {
-        self.sorted_transactions
-            .iter_mut()
-            .enumerate()
-            .rev() // slight optimisation assuming we are more likely to dispute a more recent transaction
-            .find(|(_, transaction)| transaction.id == id)
-            .map(|(index, _)| index)
+    // the current balance of an asset, zero if the client has never held it
+    pub fn state(&self, asset: Asset) -> ClientState {
+        self.states.get(&asset).copied().unwrap_or_default()
     }
 
-    pub fn calculate_funds(&mut self) -> Result<()> {
-        // TODO pass in max chronology
+    // every asset the client holds, ordered by asset
+    // a client who has never successfully transacted still has a zero balance in the default asset
+    pub fn states(&self) -> impl Iterator<Item = (Asset, ClientState)> + '_ {
+        let empty = self
+            .states
+            .is_empty()
+            .then(|| (Asset::default(), ClientState::default()));
+        self.states
+            .iter()
+            .map(|(asset, state)| (*asset, *state))
+            .chain(empty)
+    }
 
-        // combine 2 sorted lists
-        self.unsorted_transactions
-            .sort_by_key(|t| (t.chronology, t.id));
-        merge_in_place(&mut self.sorted_transactions, &self.unsorted_transactions);
+    pub fn is_locked(&self) -> bool {
+        self.locked.is_some()
+    }
 
-        // resolve any disputes we can
-        self.resolve_disputes();
+    pub fn id(&self) -> u64 {
+        self.id
+    }
 
-        let mut state = ClientState {
-            available_funds: Fixed::zero(),
-            held_funds: Fixed::zero(),
-            locked: self.locked.is_some(),
-        };
+    pub fn snapshot(&self) -> ClientSnapshot {
+        ClientSnapshot {
+            id: self.id,
+            states: self.states().collect(),
+            locked: self.is_locked(),
+        }
+    }
 
-        let max_chronology = self.locked.unwrap_or(u64::MAX);
+    // the following expose everything needed to save a client and rebuild it exactly, see engines::snapshot
 
-        // then update client state with transactions
-        for transaction in &self.sorted_transactions {
-            // account should be locked beyond any chargebacks
-            // may still have transactions beyond this point due to the async nature of the processing
-            // TODO this doesn't handle equal chronology properly
-            if transaction.chronology > max_chronology {
-                break;
-            }
+    pub(crate) fn asset_states(&self) -> &BTreeMap<Asset, ClientState> {
+        &self.states
+    }
 
-            if transaction.disputed {
-                // if withdrawal
-                if transaction.amount.is_negative() {
-                    // a disputed withdrawal isn't described in the brief so I am making assumptions
-                    // assume that available funds shouldn't change as we don't want to prematurely add funds
-                    // held funds should increase by the positive transaction amount so that if the transaction was indeed fraudulent,
-                    //  those funds can later be added to available funds
-                    state
-                        .held_funds
-                        .checked_add(&transaction.amount.abs())
-                        .ok_or(anyhow!("Overflow caused by disputed transaction"))?;
-                }
-                // if deposit
-                else {
-                    // this is allowed to result in negative available funds
-                    state
-                        .available_funds
-                        .checked_sub(&transaction.amount)
-                        .ok_or(anyhow!("Overflow caused by disputed transaction"))?;
-
-                    state
-                        .held_funds
-                        .checked_add(&transaction.amount)
-                        .ok_or(anyhow!("Overflow caused by disputed transaction"))?;
-                }
-            } else {
-                let available_funds = state
-                    .available_funds
-                    .checked_add(&transaction.amount)
-                    .ok_or(anyhow!("Overflow caused by transaction"))?;
+    pub(crate) fn locked_chronology(&self) -> Option<u64> {
+        self.locked
+    }
 
-                // available_funds only allowed to be 0 or above
-                if !available_funds.is_negative() {
-                    state.available_funds = available_funds;
-                }
-            }
+    pub(crate) fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    // a client carrying balances over from an earlier run, without the history needed for claims
+    // a locked client is locked from chronology 0, which every transaction is at or after, so it rejects all of them
+    pub(crate) fn opened(snapshot: ClientSnapshot) -> Self {
+        Self::from_parts(
+            snapshot.id,
+            snapshot.states,
+            snapshot.locked.then_some(0),
+            vec![],
+        )
+    }
+
+    // transactions must be sorted by (chronology, id), as transactions() returns them
+    pub(crate) fn from_parts(
+        id: u64,
+        states: BTreeMap<Asset, ClientState>,
+        locked: Option<u64>,
+        transactions: Vec<Transaction>,
+    ) -> Self {
+        let transaction_index = transactions
+            .iter()
+            .map(|transaction| (transaction.id, transaction.chronology))
+            .collect();
+        Self {
+            id,
+            states,
+            locked,
+            transactions,
+            transaction_index,
         }
+    }
+}
 
-        self.state = state;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::TransactionType;
+    use std::str::FromStr;
 
-        Ok(())
+    // what every ordering of claims should do, independent of how Client implements it
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Expected {
+        Normal,
+        Disputed,
+        Resolved,
+        ChargedBack,
     }
 
-    pub fn available_funds(&self) -> Fixed {
-        self.state.available_funds
+    fn expected_outcome(
+        state: Expected,
+        claim_type: ClaimType,
+        allow_redispute: bool,
+    ) -> Result<Expected, TransactionError> {
+        match (state, claim_type) {
+            (Expected::ChargedBack, _) => Err(TransactionError::AccountLocked),
+            (Expected::Normal, ClaimType::Dispute) => Ok(Expected::Disputed),
+            (Expected::Resolved, ClaimType::Dispute) if allow_redispute => Ok(Expected::Disputed),
+            (Expected::Resolved, ClaimType::Dispute) => Err(TransactionError::DisputeSettled),
+            (Expected::Disputed, ClaimType::Dispute) => Err(TransactionError::AlreadyDisputed),
+            (Expected::Disputed, ClaimType::Resolve) => Ok(Expected::Resolved),
+            (Expected::Disputed, ClaimType::Chargeback) => Ok(Expected::ChargedBack),
+            (_, ClaimType::Resolve | ClaimType::Chargeback) => Err(TransactionError::NotDisputed),
+        }
     }
 
-    pub fn held_funds(&self) -> Fixed {
-        self.state.held_funds
+    // (available, held) after depositing 10, withdrawing 4 and then applying claims to one of them
+    fn expected_funds(funds_type: FundsType, state: Expected) -> (Amount, Amount) {
+        let fixed = |s| Amount::from_str(s).unwrap();
+        match (funds_type, state) {
+            (FundsType::Deposit, Expected::Normal | Expected::Resolved) => (fixed("6"), fixed("0")),
+            (FundsType::Deposit, Expected::Disputed) => (fixed("-4"), fixed("10")),
+            (FundsType::Deposit, Expected::ChargedBack) => (fixed("-4"), fixed("0")),
+            (FundsType::Withdrawal, Expected::Normal | Expected::Resolved) => {
+                (fixed("6"), fixed("0"))
+            }
+            (FundsType::Withdrawal, Expected::Disputed) => (fixed("6"), fixed("4")),
+            (FundsType::Withdrawal, Expected::ChargedBack) => (fixed("10"), fixed("0")),
+        }
     }
 
-    pub fn total_funds(&self) -> Fixed {
-        self.available_funds() + self.held_funds()
+    fn all_sequences(max_length: usize) -> Vec<Vec<ClaimType>> {
+        let mut sequences = vec![vec![]];
+        let mut previous = vec![vec![]];
+        for _ in 0..max_length {
+            let mut next = vec![];
+            for sequence in &previous {
+                for claim_type in [
+                    ClaimType::Dispute,
+                    ClaimType::Resolve,
+                    ClaimType::Chargeback,
+                ] {
+                    let mut sequence: Vec<ClaimType> = sequence.clone();
+                    sequence.push(claim_type);
+                    next.push(sequence);
+                }
+            }
+            sequences.extend(next.iter().cloned());
+            previous = next;
+        }
+        sequences
     }
 
-    pub fn is_locked(&self) -> bool {
-        self.state.locked
+    #[test]
+    fn every_claim_ordering_follows_the_state_machine() {
+        let config_options = [true, false].map(|allow_redispute| ClientConfig { allow_redispute });
+        for config in config_options {
+            for (disputed_id, funds_type) in [(1, FundsType::Deposit), (2, FundsType::Withdrawal)] {
+                for sequence in all_sequences(4) {
+                    let mut client = Client::new(1);
+                    let amount = |s| Amount::from_str(s).unwrap();
+                    let transactions = [
+                        TransactionType::Deposit(amount("10")),
+                        TransactionType::Withdrawal(amount("4")),
+                    ];
+                    for (id, transaction_type) in transactions.into_iter().enumerate() {
+                        let id = id as u64 + 1;
+                        let transaction = UnprocessedTransaction::new(
+                            transaction_type,
+                            1,
+                            id,
+                            id,
+                            Asset::default(),
+                        );
+                        client.handle_transaction(transaction, &config).unwrap();
+                    }
+
+                    let mut state = Expected::Normal;
+                    for (i, claim_type) in sequence.iter().enumerate() {
+                        let transaction = UnprocessedTransaction::new(
+                            TransactionType::Claim(*claim_type),
+                            1,
+                            disputed_id,
+                            10 + i as u64,
+                            Asset::default(),
+                        );
+                        let expected = expected_outcome(state, *claim_type, config.allow_redispute);
+                        let result = client.handle_transaction(transaction, &config);
+                        assert_eq!(
+                            result,
+                            expected.map(|_| ()),
+                            "{config:?} {funds_type:?} {sequence:?} step {i}"
+                        );
+                        state = expected.unwrap_or(state);
+                    }
+
+                    let (available, held) = expected_funds(funds_type, state);
+                    let balance = client.state(Asset::default());
+                    assert_eq!(balance.available_funds(), available, "{sequence:?}");
+                    assert_eq!(balance.held_funds(), held, "{sequence:?}");
+                    assert_eq!(client.is_locked(), state == Expected::ChargedBack);
+                }
+            }
+        }
     }
 
-    pub fn id(&self) -> u64 {
-        self.id
+    #[test]
+    fn claims_cannot_precede_what_they_reference() {
+        let config = ClientConfig::default();
+        let mut client = Client::new(1);
+        let deposit = UnprocessedTransaction::new(
+            TransactionType::Deposit(Amount::from_str("10").unwrap()),
+            1,
+            1,
+            5,
+            Asset::default(),
+        );
+        client.handle_transaction(deposit, &config).unwrap();
+
+        let claim = |claim_type, chronology| {
+            UnprocessedTransaction::new(
+                TransactionType::Claim(claim_type),
+                1,
+                1,
+                chronology,
+                Asset::default(),
+            )
+        };
+        assert_eq!(
+            client.handle_transaction(claim(ClaimType::Dispute, 4), &config),
+            Err(TransactionError::UnknownTransaction)
+        );
+        client
+            .handle_transaction(claim(ClaimType::Dispute, 8), &config)
+            .unwrap();
+        assert_eq!(
+            client.handle_transaction(claim(ClaimType::Resolve, 7), &config),
+            Err(TransactionError::NotDisputed)
+        );
+        assert_eq!(
+            client.handle_transaction(claim(ClaimType::Chargeback, 6), &config),
+            Err(TransactionError::NotDisputed)
+        );
+        client
+            .handle_transaction(claim(ClaimType::Resolve, 9), &config)
+            .unwrap();
     }
 }
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use crate::clients::{Client, ClientConfig};
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+
+// the brief guarantees client ids fit in a u16, so the dense store is sized for that by default
+pub const DEFAULT_DENSE_CLIENT_LIMIT: u64 = u16::MAX as u64;
+
+// somewhere to keep clients, looked up by client id
+pub trait ClientStore {
+    // the client with this id, created if it doesn't exist yet
+    fn get_or_insert(&mut self, client_id: u64) -> Result<&mut Client, TransactionError>;
+
+    fn get(&self, client_id: u64) -> Option<&Client>;
+
+    // every client, ordered by client id so output is deterministic
+    fn sorted_clients(&self) -> Vec<&Client>;
+
+    // hands the transaction to its client, creating the client if it doesn't exist yet
+    fn apply(
+        &mut self,
+        transaction: UnprocessedTransaction,
+        client_config: &ClientConfig,
+    ) -> Result<&Client, TransactionError> {
+        let client = self.get_or_insert(transaction.metadata.client_id)?;
+        client.handle_transaction(transaction, client_config)?;
+        Ok(client)
+    }
+}
+
+// suitable for sparse client ids of any size
+#[derive(Default, Debug)]
+pub struct HashClientStore {
+    clients: HashMap<u64, Client>,
+}
+
+impl HashClientStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ClientStore for HashClientStore {
+    fn get_or_insert(&mut self, client_id: u64) -> Result<&mut Client, TransactionError> {
+        Ok(self
+            .clients
+            .entry(client_id)
+            .or_insert_with(|| Client::new(client_id)))
+    }
+
+    fn get(&self, client_id: u64) -> Option<&Client> {
+        self.clients.get(&client_id)
+    }
+
+    fn sorted_clients(&self) -> Vec<&Client> {
+        let mut clients: Vec<&Client> = self.clients.values().collect();
+        clients.sort_unstable_by_key(|client| client.id());
+        clients
+    }
+}
+
+// indexed by client_id (client_id 0 is allowed by this code)
+// faster than hashing for small dense client ids, client ids above the limit are rejected
+// rather than allocating space for every client id up to them
+#[derive(Debug)]
+pub struct DenseClientStore {
+    clients: Vec<Option<Client>>,
+    max_client_id: u64,
+}
+
+impl DenseClientStore {
+    pub fn new(max_client_id: u64) -> Self {
+        Self {
+            clients: vec![],
+            max_client_id,
+        }
+    }
+}
+
+impl Default for DenseClientStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_DENSE_CLIENT_LIMIT)
+    }
+}
+
+impl ClientStore for DenseClientStore {
+    fn get_or_insert(&mut self, client_id: u64) -> Result<&mut Client, TransactionError> {
+        if client_id > self.max_client_id {
+            return Err(TransactionError::ClientIdOutOfRange);
+        }
+        let index = client_id as usize;
+
+        // if client_id is off the end of the current list of clients
+        if index >= self.clients.len() {
+            self.clients.resize_with(index + 1, || None);
+        }
+
+        Ok(self.clients[index].get_or_insert_with(|| Client::new(client_id)))
+    }
+
+    fn get(&self, client_id: u64) -> Option<&Client> {
+        self.clients.get(client_id as usize)?.as_ref()
+    }
+
+    fn sorted_clients(&self) -> Vec<&Client> {
+        self.clients.iter().flatten().collect()
+    }
+}
@@ -1,2 +1,5 @@
 pub mod client;
-pub use client::Client;
+pub use client::{Client, ClientConfig, ClientSnapshot};
+
+pub mod client_store;
+pub use client_store::{ClientStore, DenseClientStore, HashClientStore};
@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+use tokio::sync::{Mutex, mpsc, oneshot};
+
+use crate::clients::{Client, ClientConfig, ClientSnapshot, ClientStore, HashClientStore};
+use crate::engines::TransactionRegistry;
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+
+// how many requests can be queued for a shard before submitting waits
+const CHANNEL_CAPACITY: usize = 1024;
+
+// the client's balances straight after a transaction was applied
+pub type Outcome = ClientSnapshot;
+
+enum ShardRequest {
+    Apply(
+        UnprocessedTransaction,
+        oneshot::Sender<Result<Outcome, TransactionError>>,
+    ),
+    Snapshot(u64, oneshot::Sender<Option<ClientSnapshot>>),
+}
+
+// an async handle to an engine, for embedding in tokio services
+// clients are sharded by client id across tasks, so transactions for different clients are applied concurrently
+// the handle is cheap to clone, and the shard tasks stop once every handle has been dropped
+// there's no reorder window, transactions are applied in the order they're submitted
+#[derive(Clone)]
+pub struct AsyncEngine {
+    shards: Vec<mpsc::Sender<ShardRequest>>,
+    registry: Arc<Mutex<TransactionRegistry>>,
+}
+
+impl AsyncEngine {
+    // spawns the shard tasks, so has to be called from within a tokio runtime
+    pub fn new(shards: usize, client_config: ClientConfig) -> Self {
+        let shards = (0..shards.max(1))
+            .map(|_| {
+                let (sender, mut receiver) = mpsc::channel(CHANNEL_CAPACITY);
+                tokio::spawn(async move {
+                    let mut clients = HashClientStore::new();
+                    while let Some(request) = receiver.recv().await {
+                        match request {
+                            ShardRequest::Apply(transaction, reply) => {
+                                let outcome = clients
+                                    .apply(transaction, &client_config)
+                                    .map(Client::snapshot);
+                                let _ = reply.send(outcome);
+                            }
+                            ShardRequest::Snapshot(client_id, reply) => {
+                                let _ = reply.send(clients.get(client_id).map(Client::snapshot));
+                            }
+                        }
+                    }
+                });
+                sender
+            })
+            .collect();
+
+        Self {
+            shards,
+            registry: Arc::new(Mutex::new(TransactionRegistry::new())),
+        }
+    }
+
+    // resolves once the transaction has been applied or rejected
+    //
+    // cancel safety: if the future is dropped while waiting for room in the shard's queue or for the registry,
+    // nothing has happened, the transaction id isn't taken and it can be submitted again
+    // once queued the transaction will be applied even if the future is dropped, only its outcome is lost
+    pub async fn submit(
+        &self,
+        transaction: UnprocessedTransaction,
+    ) -> Result<Outcome, TransactionError> {
+        let (reply, outcome) = oneshot::channel();
+        // room is reserved before taking the registry, so a full shard doesn't hold up submits to the others
+        let permit = self
+            .shard(transaction.metadata.client_id)
+            .reserve()
+            .await
+            .expect("Shard task stopped");
+        {
+            // held until the transaction is queued, so shards see transactions in the order the registry did
+            // there's no await between taking the id and queueing it
+            let mut registry = self.registry.lock().await;
+            registry.check(&transaction)?;
+            permit.send(ShardRequest::Apply(transaction, reply));
+        }
+
+        outcome.await.expect("Shard task stopped")
+    }
+
+    // includes every transaction submitted before this was called
+    pub async fn snapshot(&self, client_id: u64) -> Option<ClientSnapshot> {
+        let (reply, snapshot) = oneshot::channel();
+        self.shard(client_id)
+            .send(ShardRequest::Snapshot(client_id, reply))
+            .await
+            .expect("Shard task stopped");
+
+        snapshot.await.expect("Shard task stopped")
+    }
+
+    fn shard(&self, client_id: u64) -> &mpsc::Sender<ShardRequest> {
+        &self.shards[(client_id % self.shards.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::Engine;
+    use crate::transactions::transaction::ClaimType;
+    use crate::transactions::{Amount, Asset, TransactionType};
+    use std::future::{Future, poll_fn};
+    use std::pin::{Pin, pin};
+    use std::str::FromStr;
+    use std::task::Poll;
+    use tokio::task::unconstrained;
+
+    // unconstrained, otherwise tokio's cooperative budget can make it wait even though it's able to carry on
+    async fn poll_once<F: Future>(mut future: Pin<&mut F>) -> bool {
+        unconstrained(poll_fn(|cx| {
+            Poll::Ready(future.as_mut().poll(cx).is_ready())
+        }))
+        .await
+    }
+
+    // polls once then drops the future, as a timeout firing would
+    async fn poll_once_then_cancel(future: impl Future) -> bool {
+        poll_once(pin!(future)).await
+    }
+
+    fn transaction(
+        transaction_type: TransactionType,
+        client_id: u64,
+        transaction_id: u64,
+        chronology: u64,
+    ) -> UnprocessedTransaction {
+        UnprocessedTransaction::new(
+            transaction_type,
+            client_id,
+            transaction_id,
+            chronology,
+            Asset::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn matches_the_synchronous_engine() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let transactions = [
+            (TransactionType::Deposit(amount("10")), 1, 1),
+            (TransactionType::Deposit(amount("5")), 2, 2),
+            (TransactionType::Withdrawal(amount("20")), 1, 3),
+            (TransactionType::Deposit(amount("1")), 3, 2),
+            (TransactionType::Claim(ClaimType::Dispute), 1, 2),
+            (TransactionType::Claim(ClaimType::Dispute), 2, 2),
+            (TransactionType::Withdrawal(amount("1")), 2, 4),
+            (TransactionType::Claim(ClaimType::Chargeback), 2, 2),
+            (TransactionType::Deposit(amount("3")), 2, 5),
+            (TransactionType::Claim(ClaimType::Dispute), 1, 1),
+            (TransactionType::Claim(ClaimType::Resolve), 1, 1),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(
+            |(chronology, (transaction_type, client_id, transaction_id))| {
+                transaction(
+                    transaction_type,
+                    client_id,
+                    transaction_id,
+                    chronology as u64,
+                )
+            },
+        );
+
+        let mut engine = Engine::new();
+        let async_engine = AsyncEngine::new(2, ClientConfig::default());
+        for transaction in transactions {
+            let expected = engine.apply(transaction).map(|()| {
+                engine
+                    .client(transaction.metadata.client_id)
+                    .unwrap()
+                    .snapshot()
+            });
+            assert_eq!(async_engine.submit(transaction).await, expected);
+        }
+
+        for client_id in 1..=3 {
+            assert_eq!(
+                async_engine.snapshot(client_id).await,
+                engine.client(client_id).map(Client::snapshot)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_chargeback_locks_out_transactions_without_chronologies() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let async_engine = AsyncEngine::new(1, ClientConfig::default());
+        for transaction in [
+            UnprocessedTransaction::deposit(1, 1, amount("10")),
+            UnprocessedTransaction::dispute(1, 1),
+            UnprocessedTransaction::chargeback(1, 1),
+        ] {
+            async_engine.submit(transaction).await.unwrap();
+        }
+
+        for transaction in [
+            UnprocessedTransaction::deposit(1, 2, amount("5")),
+            UnprocessedTransaction::withdrawal(1, 3, amount("5")),
+        ] {
+            assert_eq!(
+                async_engine.submit(transaction).await,
+                Err(TransactionError::AccountLocked)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_while_the_queue_is_full_leaves_the_id_free() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let async_engine = AsyncEngine::new(1, ClientConfig::default());
+
+        // the shard task can't run until this task waits, so every deposit stays queued
+        for id in 0..CHANNEL_CAPACITY as u64 {
+            let deposit = UnprocessedTransaction::deposit(1, id, amount("1"));
+            assert!(!poll_once_then_cancel(async_engine.submit(deposit)).await);
+        }
+        let deposit = UnprocessedTransaction::deposit(1, CHANNEL_CAPACITY as u64, amount("1"));
+        assert!(!poll_once_then_cancel(async_engine.submit(deposit)).await);
+
+        // queued transactions are still applied, and the cancelled one can be submitted again
+        let client = async_engine.submit(deposit).await.unwrap();
+        assert_eq!(
+            client.states[&Asset::default()].available_funds(),
+            amount(&(CHANNEL_CAPACITY + 1).to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn a_full_shard_doesnt_hold_up_the_others() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let async_engine = AsyncEngine::new(2, ClientConfig::default());
+
+        // client 2 is on the first shard, which can't run until this task waits, so its queue fills up
+        for id in 0..CHANNEL_CAPACITY as u64 {
+            let deposit = UnprocessedTransaction::deposit(2, id, amount("1"));
+            assert!(!poll_once_then_cancel(async_engine.submit(deposit)).await);
+        }
+        let deposit = UnprocessedTransaction::deposit(2, CHANNEL_CAPACITY as u64, amount("1"));
+        let mut blocked = pin!(async_engine.submit(deposit));
+        assert!(!poll_once(blocked.as_mut()).await);
+
+        // client 1 is on the second shard, its deposit is queued while the first shard is still full
+        let deposit = UnprocessedTransaction::deposit(1, CHANNEL_CAPACITY as u64 + 1, amount("1"));
+        let mut other = pin!(async_engine.submit(deposit));
+        assert!(!poll_once(other.as_mut()).await);
+        let client = async_engine.snapshot(1).await.unwrap();
+        assert_eq!(
+            client.states[&Asset::default()].available_funds(),
+            amount("1")
+        );
+
+        assert!(other.await.is_ok());
+        assert!(blocked.await.is_ok());
+    }
+}
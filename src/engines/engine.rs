@@ -0,0 +1,314 @@
+use std::io::{Read, Write};
+
+use anyhow::{Result, anyhow};
+
+use crate::clients::{Client, ClientConfig, ClientSnapshot, ClientStore, HashClientStore};
+use crate::engines::reorder_buffer::order_key;
+use crate::engines::snapshot::{decode_snapshot, encode_snapshot};
+use crate::engines::{ReorderBuffer, TransactionRegistry};
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+
+// applies a stream of transactions to clients one at a time
+// the only memory held is per-client history needed for later disputes, and the reorder window
+//
+// transactions can either be applied immediately with apply, or put in chronology order first:
+// submit each transaction, then poll until it returns None to apply any which have left the reorder window
+// once the input is exhausted, call finish then poll until None to apply the rest
+#[derive(Default, Debug)]
+pub struct Engine<S: ClientStore = HashClientStore> {
+    clients: S,
+    // checks transaction ids across all clients
+    registry: TransactionRegistry,
+    client_config: ClientConfig,
+    reorder_buffer: ReorderBuffer,
+    finished: bool,
+    // the order key of the last transaction replayed from an earlier run
+    replayed_until: Option<(u64, u32, u64)>,
+    // one past the latest chronology applied
+    next_chronology: u64,
+    // added to the chronology of submitted transactions, so those after a restored snapshot come after it
+    chronology_offset: u64,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: ClientStore> Engine<S> {
+    pub fn with_store(clients: S) -> Self {
+        Self {
+            clients,
+            registry: TransactionRegistry::new(),
+            client_config: ClientConfig::default(),
+            reorder_buffer: ReorderBuffer::default(),
+            finished: false,
+            replayed_until: None,
+            next_chronology: 0,
+            chronology_offset: 0,
+        }
+    }
+
+    // how many transactions can be held back to put them in chronology order, 0 by default
+    pub fn with_reorder_window(mut self, window: usize) -> Self {
+        self.reorder_buffer = ReorderBuffer::new(window);
+        self
+    }
+
+    pub fn with_client_config(mut self, client_config: ClientConfig) -> Self {
+        self.client_config = client_config;
+        self
+    }
+
+    // a rejected transaction leaves balances unchanged, but its chronology still counts, and a deposit or withdrawal
+    // rejected by its client still takes its id, the caller decides what to do with the rejection
+    pub fn apply(&mut self, transaction: UnprocessedTransaction) -> Result<(), TransactionError> {
+        let chronology = transaction.metadata.chronology;
+        self.next_chronology = self.next_chronology.max(chronology.saturating_add(1));
+
+        self.registry.check(&transaction)?;
+
+        self.clients.apply(transaction, &self.client_config)?;
+        Ok(())
+    }
+
+    // applies every transaction an earlier run applied, e.g. recovered from a write-ahead log, in the order it applied them
+    // rejected ones have to be included as well, they can still have taken their ids
+    // the same ones are rejected again, and are returned so they can be reported again
+    // input at or before the last of them should then be skipped, see is_replayed
+    pub fn replay(
+        &mut self,
+        transactions: impl IntoIterator<Item = UnprocessedTransaction>,
+    ) -> Vec<(UnprocessedTransaction, TransactionError)> {
+        let mut rejections = vec![];
+        for transaction in transactions {
+            if let Err(err) = self.apply(transaction) {
+                rejections.push((transaction, err));
+            }
+            self.reorder_buffer.resume_after(&transaction);
+            self.replayed_until = Some(order_key(&transaction));
+        }
+        rejections
+    }
+
+    // whether the earlier run had already got past this transaction, so it shouldn't be submitted again
+    // transactions the earlier run rejected on submit never reached apply, but land before the last replayed one so are
+    // skipped too
+    pub fn is_replayed(&self, transaction: &UnprocessedTransaction) -> bool {
+        self.replayed_until.is_some_and(|replayed_until| {
+            order_key(&self.after_snapshot(*transaction)) <= replayed_until
+        })
+    }
+
+    // only fails if the transaction arrived too late to be put in order
+    pub fn submit(&mut self, transaction: UnprocessedTransaction) -> Result<(), TransactionError> {
+        self.reorder_buffer.push(self.after_snapshot(transaction))
+    }
+
+    fn after_snapshot(&self, mut transaction: UnprocessedTransaction) -> UnprocessedTransaction {
+        let metadata = &mut transaction.metadata;
+        metadata.chronology = metadata.chronology.saturating_add(self.chronology_offset);
+        transaction
+    }
+
+    // writes every client's balances, lock and the history needed for later claims, along with every taken transaction id
+    // anything still in the reorder window can't be included, so finish and poll until None first
+    pub fn snapshot<W: Write>(&self, mut writer: W) -> Result<()> {
+        if !self.reorder_buffer.is_empty() {
+            return Err(anyhow!(
+                "Can't snapshot while transactions are waiting in the reorder window"
+            ));
+        }
+
+        // sorted rather than in hash map order, so the same state always makes the same bytes
+        let mut owners: Vec<(u64, u64)> = self.registry.owners().collect();
+        owners.sort_unstable();
+        let bytes = encode_snapshot(
+            self.next_chronology,
+            owners.into_iter(),
+            &self.clients.sorted_clients(),
+        );
+        writer.write_all(&bytes)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // carries on from a snapshot, into an engine which hasn't been given any transactions yet
+    // submitted transactions are placed after everything in the snapshot, so a tail starting from row 0 still
+    // comes after it, transactions applied directly with apply or replay are taken as they are
+    pub fn restore<R: Read>(&mut self, mut reader: R) -> Result<()> {
+        if !self.is_new() {
+            return Err(anyhow!("Can only restore a snapshot into a new engine"));
+        }
+
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let contents = decode_snapshot(&bytes)?;
+
+        for client in contents.clients {
+            let id = client.id();
+            *self
+                .clients
+                .get_or_insert(id)
+                .map_err(|err| anyhow!("Can't restore client {id} from the snapshot: {err}"))? =
+                client;
+        }
+        self.registry = TransactionRegistry::from_owners(contents.owners);
+        self.next_chronology = contents.next_chronology;
+        self.chronology_offset = contents.next_chronology;
+        Ok(())
+    }
+
+    // starts clients from balances carried over from an earlier run, into an engine which hasn't been given any transactions yet
+    // the balances count as chronology 0 and submitted transactions come after them, the same as after a restore,
+    // a client which starts locked rejects every transaction, whether submitted or applied directly
+    pub fn open_balances(
+        &mut self,
+        clients: impl IntoIterator<Item = ClientSnapshot>,
+    ) -> Result<()> {
+        if !self.is_new() {
+            return Err(anyhow!("Can only open balances in a new engine"));
+        }
+
+        for client in clients {
+            let id = client.id;
+            *self
+                .clients
+                .get_or_insert(id)
+                .map_err(|err| anyhow!("Can't open a balance for client {id}: {err}"))? =
+                Client::opened(client);
+        }
+        self.next_chronology = 1;
+        self.chronology_offset = 1;
+        Ok(())
+    }
+
+    fn is_new(&self) -> bool {
+        self.next_chronology == 0 && self.reorder_buffer.is_empty()
+    }
+
+    // applies the next transaction to leave the reorder window, returning it along with the result of applying it
+    pub fn poll(&mut self) -> Option<(UnprocessedTransaction, Result<(), TransactionError>)> {
+        let transaction = self.reorder_buffer.pop(self.finished)?;
+        Some((transaction, self.apply(transaction)))
+    }
+
+    // no more transactions will be submitted, so everything left in the reorder window can be applied
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub fn client(&self, client_id: u64) -> Option<&Client> {
+        self.clients.get(client_id)
+    }
+
+    // ordered by client id
+    pub fn clients(&self) -> Vec<&Client> {
+        self.clients.sorted_clients()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::{Amount, Asset};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn a_chargeback_locks_out_transactions_without_chronologies() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let mut engine = Engine::new();
+        engine
+            .apply(UnprocessedTransaction::deposit(1, 1, amount("10")))
+            .unwrap();
+        engine
+            .apply(UnprocessedTransaction::deposit(1, 2, amount("10")))
+            .unwrap();
+        engine.apply(UnprocessedTransaction::dispute(1, 1)).unwrap();
+        engine
+            .apply(UnprocessedTransaction::chargeback(1, 1))
+            .unwrap();
+
+        for transaction in [
+            UnprocessedTransaction::deposit(1, 3, amount("5")),
+            UnprocessedTransaction::withdrawal(1, 4, amount("5")),
+            UnprocessedTransaction::dispute(1, 2),
+        ] {
+            assert_eq!(
+                engine.apply(transaction),
+                Err(TransactionError::AccountLocked)
+            );
+        }
+        let client = engine.client(1).unwrap();
+        assert!(client.is_locked());
+        assert_eq!(
+            client.state(Default::default()).available_funds(),
+            amount("10")
+        );
+    }
+
+    #[test]
+    fn replaying_rejected_transactions_takes_their_ids_again() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let applied = [
+            // rejected by the client, but the id is still taken
+            UnprocessedTransaction::withdrawal(1, 2, amount("100")).with_chronology(0),
+            UnprocessedTransaction::deposit(1, 1, amount("10")).with_chronology(1),
+        ];
+        let mut engine = Engine::new();
+        let rejections = engine.replay(applied);
+        assert_eq!(rejections.len(), 1);
+        assert_eq!(rejections[0].1, TransactionError::InsufficientFunds);
+
+        let resumed = UnprocessedTransaction::deposit(1, 2, amount("5")).with_chronology(2);
+        assert!(!engine.is_replayed(&resumed));
+        assert_eq!(
+            engine.apply(resumed),
+            Err(TransactionError::DuplicateTransactionId)
+        );
+        assert_eq!(
+            engine
+                .client(1)
+                .unwrap()
+                .state(Default::default())
+                .available_funds(),
+            amount("10")
+        );
+    }
+
+    #[test]
+    fn clients_opened_locked_reject_everything() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let mut engine = Engine::new();
+        engine
+            .open_balances([ClientSnapshot {
+                id: 3,
+                states: BTreeMap::new(),
+                locked: true,
+            }])
+            .unwrap();
+
+        assert_eq!(
+            engine.apply(UnprocessedTransaction::deposit(3, 1, amount("5"))),
+            Err(TransactionError::AccountLocked)
+        );
+        engine
+            .submit(UnprocessedTransaction::deposit(3, 2, amount("5")))
+            .unwrap();
+        engine.finish();
+        assert_eq!(
+            engine.poll().map(|(_, result)| result),
+            Some(Err(TransactionError::AccountLocked))
+        );
+        assert_eq!(
+            engine
+                .client(3)
+                .unwrap()
+                .state(Asset::default())
+                .available_funds(),
+            amount("0")
+        );
+    }
+}
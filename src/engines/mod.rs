@@ -0,0 +1,18 @@
+#[cfg(feature = "async")]
+pub mod async_engine;
+#[cfg(feature = "async")]
+pub use async_engine::AsyncEngine;
+
+pub mod engine;
+pub use engine::Engine;
+
+pub mod reorder_buffer;
+pub use reorder_buffer::ReorderBuffer;
+
+pub mod sharded_engine;
+pub use sharded_engine::ShardedEngine;
+
+mod snapshot;
+
+pub mod transaction_registry;
+pub use transaction_registry::TransactionRegistry;
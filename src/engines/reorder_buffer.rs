@@ -0,0 +1,90 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+
+// inputs may arrive slightly out of order, this holds up to window transactions
+// and releases them in chronology order (ties keep their input order, earlier inputs first)
+// a transaction which is earlier than one already released can no longer be put in order, so is rejected
+#[derive(Default, Debug)]
+pub struct ReorderBuffer {
+    window: usize,
+    pending: BinaryHeap<Reverse<Pending>>,
+    last_released: Option<(u64, u32, u64)>,
+}
+
+#[derive(Debug)]
+struct Pending(UnprocessedTransaction);
+
+impl Pending {
+    fn key(&self) -> (u64, u32, u64) {
+        order_key(&self.0)
+    }
+}
+
+// the order transactions are applied in, ties broken by input then row
+pub fn order_key(transaction: &UnprocessedTransaction) -> (u64, u32, u64) {
+    let metadata = &transaction.metadata;
+    (metadata.chronology, metadata.source, metadata.row)
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl ReorderBuffer {
+    // a window of 0 releases every transaction immediately
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, transaction: UnprocessedTransaction) -> Result<(), TransactionError> {
+        if let Some(last_released) = self.last_released
+            && order_key(&transaction) < last_released
+        {
+            return Err(TransactionError::LateArrival);
+        }
+
+        self.pending.push(Reverse(Pending(transaction)));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // treats transaction as the last one released, e.g. when it was applied in an earlier run
+    pub fn resume_after(&mut self, transaction: &UnprocessedTransaction) {
+        self.last_released = Some(order_key(transaction));
+    }
+
+    // the earliest transaction, once the window is full or when flushing the remaining transactions
+    pub fn pop(&mut self, flush: bool) -> Option<UnprocessedTransaction> {
+        if !flush && self.pending.len() <= self.window {
+            return None;
+        }
+
+        let Reverse(Pending(transaction)) = self.pending.pop()?;
+        self.last_released = Some(order_key(&transaction));
+        Some(transaction)
+    }
+}
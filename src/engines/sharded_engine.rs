@@ -0,0 +1,156 @@
+use std::sync::mpsc::{SyncSender, sync_channel};
+use std::thread::{self, JoinHandle};
+
+use crate::clients::{Client, ClientConfig, ClientStore, HashClientStore};
+use crate::engines::{ReorderBuffer, TransactionRegistry};
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+
+// transactions are sent to shards in batches to keep channel overhead down
+const BATCH_SIZE: usize = 1024;
+// how many batches can be queued for a shard before the sender blocks, bounding memory
+const CHANNEL_CAPACITY: usize = 64;
+
+// numbers every applied or rejected transaction in the order the single threaded Engine would have handled it,
+// so rejections from every shard can be put back into that order
+pub type Sequenced<T> = (u64, T);
+
+pub type Rejection = (UnprocessedTransaction, TransactionError);
+
+// produces the same results as Engine, but applies transactions on multiple threads
+// clients are sharded by client id so each client's transactions are applied in order on a single thread,
+// the reorder window and global transaction id checks stay on the calling thread
+pub struct ShardedEngine<S: ClientStore = HashClientStore> {
+    senders: Vec<SyncSender<Vec<Sequenced<UnprocessedTransaction>>>>,
+    workers: Vec<JoinHandle<Shard<S>>>,
+    batches: Vec<Vec<Sequenced<UnprocessedTransaction>>>,
+    registry: TransactionRegistry,
+    reorder_buffer: ReorderBuffer,
+    sequence: u64,
+    rejections: Vec<Sequenced<Rejection>>,
+}
+
+struct Shard<S: ClientStore> {
+    clients: S,
+    rejections: Vec<Sequenced<Rejection>>,
+}
+
+// everything left once a ShardedEngine has finished
+pub struct ShardedOutcome {
+    // ordered by client id
+    pub clients: Vec<Client>,
+    // numbered in the order Engine would have rejected them
+    pub rejections: Vec<Sequenced<Rejection>>,
+}
+
+impl<S: ClientStore + Default + Send + 'static> ShardedEngine<S> {
+    pub fn new(threads: usize, client_config: ClientConfig, reorder_window: usize) -> Self {
+        let threads = threads.max(1);
+        let mut senders = Vec::with_capacity(threads);
+        let mut workers = Vec::with_capacity(threads);
+
+        for _ in 0..threads {
+            let (sender, receiver) =
+                sync_channel::<Vec<Sequenced<UnprocessedTransaction>>>(CHANNEL_CAPACITY);
+            senders.push(sender);
+            workers.push(thread::spawn(move || {
+                let mut shard = Shard {
+                    clients: S::default(),
+                    rejections: vec![],
+                };
+                // runs until the sender is dropped
+                for batch in receiver {
+                    for (sequence, transaction) in batch {
+                        if let Err(err) = shard.clients.apply(transaction, &client_config) {
+                            shard.rejections.push((sequence, (transaction, err)));
+                        }
+                    }
+                }
+                shard
+            }));
+        }
+
+        Self {
+            senders,
+            workers,
+            batches: vec![Vec::with_capacity(BATCH_SIZE); threads],
+            registry: TransactionRegistry::new(),
+            reorder_buffer: ReorderBuffer::new(reorder_window),
+            sequence: 0,
+            rejections: vec![],
+        }
+    }
+
+    pub fn submit(&mut self, transaction: UnprocessedTransaction) {
+        if let Err(err) = self.reorder_buffer.push(transaction) {
+            self.reject(transaction, err);
+        }
+        self.dispatch_ready(false);
+    }
+
+    // waits for every shard to finish applying transactions
+    pub fn finish(mut self) -> ShardedOutcome {
+        self.dispatch_ready(true);
+        for shard_index in 0..self.senders.len() {
+            self.send_batch(shard_index);
+        }
+        // closing the channels stops the workers
+        self.senders.clear();
+
+        let mut clients = vec![];
+        let mut rejections = std::mem::take(&mut self.rejections);
+        for worker in self.workers.drain(..) {
+            let shard = worker.join().expect("Shard worker thread panicked");
+            clients.extend(shard.clients.sorted_clients().into_iter().cloned());
+            rejections.extend(shard.rejections);
+        }
+
+        clients.sort_unstable_by_key(|client| client.id());
+        rejections.sort_unstable_by_key(|(sequence, _)| *sequence);
+
+        ShardedOutcome {
+            clients,
+            rejections,
+        }
+    }
+
+    fn dispatch_ready(&mut self, flush: bool) {
+        while let Some(transaction) = self.reorder_buffer.pop(flush) {
+            if let Err(err) = self.registry.check(&transaction) {
+                self.reject(transaction, err);
+                continue;
+            }
+
+            let shard_index = (transaction.metadata.client_id % self.senders.len() as u64) as usize;
+            let sequence = self.next_sequence();
+            self.batches[shard_index].push((sequence, transaction));
+            if self.batches[shard_index].len() >= BATCH_SIZE {
+                self.send_batch(shard_index);
+            }
+        }
+    }
+
+    fn send_batch(&mut self, shard_index: usize) {
+        if self.batches[shard_index].is_empty() {
+            return;
+        }
+
+        let batch = std::mem::replace(
+            &mut self.batches[shard_index],
+            Vec::with_capacity(BATCH_SIZE),
+        );
+        // a worker only stops early if it panicked, which finish reports
+        let _ = self.senders[shard_index].send(batch);
+    }
+
+    fn reject(&mut self, transaction: UnprocessedTransaction, err: TransactionError) {
+        let sequence = self.next_sequence();
+        self.rejections.push((sequence, (transaction, err)));
+    }
+
+    // reserves a place in the rejection order for something rejected before reaching the engine, e.g. a row
+    // which failed to parse
+    pub fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+}
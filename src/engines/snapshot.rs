@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+
+use crate::clients::Client;
+use crate::clients::client::ClientState;
+use crate::transactions::{Amount, Asset, DisputeState, FundsType, Transaction};
+use crate::util::varint::{read_signed_varint, read_varint, write_signed_varint, write_varint};
+
+// everything an engine needs to carry on from where it was, see Engine::snapshot
+//
+// header: magic "KSNP", u16 version, u16 reserved
+// then the body, ending in a u32 crc32 of everything before it (header included)
+// body, all varints (zigzag for balances and amounts):
+//   next chronology
+//   registry: count, then (transaction id, client id) for each
+//   clients: count, then for each
+//     id, locked (0 if not, otherwise its chronology + 1)
+//     balances: count, then (asset, available, held) for each
+//     deposits and withdrawals in (chronology, id) order: count, then for each
+//       id, chronology, funds type (0 deposit, 1 withdrawal), asset, amount, dispute state
+// an asset is its length then its bytes, a dispute state is 0 for normal, or 1 disputed, 2 resolved,
+// 3 charged back followed by the claim's chronology
+// balances and amounts are raw, so restoring with different --asset-scale settings changes their value
+// everything else is little endian
+const MAGIC: &[u8; 4] = b"KSNP";
+const VERSION: u16 = 1;
+const HEADER_LENGTH: usize = 8;
+const CHECKSUM_LENGTH: usize = 4;
+
+pub(crate) struct SnapshotContents {
+    // chronologies at or after this come after everything in the snapshot
+    pub next_chronology: u64,
+    // the transaction registry's owners
+    pub owners: Vec<(u64, u64)>,
+    pub clients: Vec<Client>,
+}
+
+pub(crate) fn encode_snapshot(
+    next_chronology: u64,
+    owners: impl ExactSizeIterator<Item = (u64, u64)>,
+    clients: &[&Client],
+) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+
+    let put = |bytes: &mut Vec<u8>, value: u64| write_varint(bytes, value as u128);
+
+    put(&mut bytes, next_chronology);
+
+    put(&mut bytes, owners.len() as u64);
+    for (transaction_id, client_id) in owners {
+        put(&mut bytes, transaction_id);
+        put(&mut bytes, client_id);
+    }
+
+    put(&mut bytes, clients.len() as u64);
+    for client in clients {
+        put(&mut bytes, client.id());
+        put(
+            &mut bytes,
+            client.locked_chronology().map_or(0, |locked| locked + 1),
+        );
+
+        let states = client.asset_states();
+        put(&mut bytes, states.len() as u64);
+        for (asset, state) in states {
+            put_asset(&mut bytes, asset);
+            write_signed_varint(&mut bytes, state.available_funds().raw());
+            write_signed_varint(&mut bytes, state.held_funds().raw());
+        }
+
+        let transactions = client.transactions();
+        put(&mut bytes, transactions.len() as u64);
+        for transaction in transactions {
+            put(&mut bytes, transaction.id);
+            put(&mut bytes, transaction.chronology);
+            bytes.push(match transaction.funds_type {
+                FundsType::Deposit => 0,
+                FundsType::Withdrawal => 1,
+            });
+            put_asset(&mut bytes, &transaction.asset);
+            write_signed_varint(&mut bytes, transaction.amount.raw());
+            match transaction.dispute_state {
+                DisputeState::Normal => bytes.push(0),
+                DisputeState::Disputed(chronology) => {
+                    bytes.push(1);
+                    put(&mut bytes, chronology);
+                }
+                DisputeState::Resolved(chronology) => {
+                    bytes.push(2);
+                    put(&mut bytes, chronology);
+                }
+                DisputeState::ChargedBack(chronology) => {
+                    bytes.push(3);
+                    put(&mut bytes, chronology);
+                }
+            }
+        }
+    }
+
+    let checksum = crc32fast::hash(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+fn put_asset(bytes: &mut Vec<u8>, asset: &Asset) {
+    let asset = asset.as_str().as_bytes();
+    bytes.push(asset.len() as u8);
+    bytes.extend_from_slice(asset);
+}
+
+pub(crate) fn decode_snapshot(bytes: &[u8]) -> Result<SnapshotContents> {
+    if bytes.len() < HEADER_LENGTH + CHECKSUM_LENGTH || &bytes[..4] != MAGIC {
+        return Err(anyhow!("Not a snapshot"));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(anyhow!(
+            "Snapshot is version {version}, only version {VERSION} can be restored"
+        ));
+    }
+    let (contents, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+    if crc32fast::hash(contents).to_le_bytes() != checksum {
+        return Err(anyhow!("Snapshot is corrupt, its checksum doesn't match"));
+    }
+
+    let mut decoder = Decoder {
+        bytes: contents,
+        at: HEADER_LENGTH,
+    };
+
+    let next_chronology = decoder.u64()?;
+
+    let owner_count = decoder.u64()?;
+    let owners = (0..owner_count)
+        .map(|_| Ok((decoder.u64()?, decoder.u64()?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let client_count = decoder.u64()?;
+    let mut clients = vec![];
+    for _ in 0..client_count {
+        let id = decoder.u64()?;
+        let locked = decoder.u64()?.checked_sub(1);
+
+        let mut states = BTreeMap::new();
+        for _ in 0..decoder.u64()? {
+            let asset = decoder.asset()?;
+            let state = ClientState::new(decoder.amount()?, decoder.amount()?);
+            states.insert(asset, state);
+        }
+
+        let mut transactions = vec![];
+        for _ in 0..decoder.u64()? {
+            let id = decoder.u64()?;
+            let chronology = decoder.u64()?;
+            let funds_type = match decoder.u8()? {
+                0 => FundsType::Deposit,
+                1 => FundsType::Withdrawal,
+                funds_type => return Err(anyhow!("Unknown funds type {funds_type} in snapshot")),
+            };
+            let mut transaction = Transaction::new(
+                id,
+                chronology,
+                funds_type,
+                decoder.asset()?,
+                decoder.amount()?,
+            );
+            transaction.dispute_state = match decoder.u8()? {
+                0 => DisputeState::Normal,
+                1 => DisputeState::Disputed(decoder.u64()?),
+                2 => DisputeState::Resolved(decoder.u64()?),
+                3 => DisputeState::ChargedBack(decoder.u64()?),
+                state => return Err(anyhow!("Unknown dispute state {state} in snapshot")),
+            };
+            transactions.push(transaction);
+        }
+        if !transactions.is_sorted_by(|a, b| a <= b) {
+            return Err(anyhow!(
+                "Client {id}'s transactions are out of order in snapshot"
+            ));
+        }
+
+        clients.push(Client::from_parts(id, states, locked, transactions));
+    }
+
+    if decoder.at != contents.len() {
+        return Err(anyhow!("Snapshot has unexpected bytes after its contents"));
+    }
+
+    Ok(SnapshotContents {
+        next_chronology,
+        owners,
+        clients,
+    })
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Decoder<'_> {
+    fn u8(&mut self) -> Result<u8> {
+        let byte = *self.bytes.get(self.at).ok_or_else(Self::too_short)?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let value = read_varint(self.bytes, &mut self.at).ok_or_else(Self::too_short)?;
+        Ok(u64::try_from(value)?)
+    }
+
+    fn amount(&mut self) -> Result<Amount> {
+        let raw = read_signed_varint(self.bytes, &mut self.at).ok_or_else(Self::too_short)?;
+        Ok(Amount::from_raw(raw))
+    }
+
+    fn asset(&mut self) -> Result<Asset> {
+        let length = self.u8()? as usize;
+        let asset = self
+            .bytes
+            .get(self.at..self.at + length)
+            .ok_or_else(Self::too_short)?;
+        self.at += length;
+        Asset::from_str(std::str::from_utf8(asset)?)
+    }
+
+    fn too_short() -> anyhow::Error {
+        anyhow!("Snapshot is shorter than its contents")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engines::Engine;
+    use crate::transactions::{Amount, UnprocessedTransaction};
+    use std::str::FromStr;
+
+    // chronologies start from 0 in both halves, as they would reading two files
+    fn halves() -> [Vec<UnprocessedTransaction>; 2] {
+        let amount = |s| Amount::from_str(s).unwrap();
+        [
+            vec![
+                UnprocessedTransaction::deposit(1, 1, amount("10")),
+                UnprocessedTransaction::deposit(2, 2, amount("5")),
+                UnprocessedTransaction::dispute(2, 2),
+                UnprocessedTransaction::deposit(3, 3, amount("7")),
+                UnprocessedTransaction::dispute(3, 3),
+                UnprocessedTransaction::chargeback(3, 3),
+                // rejected, but the id is still taken
+                UnprocessedTransaction::withdrawal(1, 4, amount("100")),
+            ],
+            vec![
+                UnprocessedTransaction::dispute(1, 1),
+                UnprocessedTransaction::resolve(2, 2),
+                UnprocessedTransaction::deposit(3, 5, amount("1")),
+                UnprocessedTransaction::deposit(1, 4, amount("1")),
+                UnprocessedTransaction::deposit(1, 6, amount("2")),
+            ],
+        ]
+    }
+
+    fn run(engine: &mut Engine, transactions: &[UnprocessedTransaction]) -> Vec<bool> {
+        let mut accepted = vec![];
+        for (chronology, transaction) in transactions.iter().enumerate() {
+            engine
+                .submit(transaction.with_chronology(chronology as u64))
+                .unwrap();
+            while let Some((_, result)) = engine.poll() {
+                accepted.push(result.is_ok());
+            }
+        }
+        accepted
+    }
+
+    #[test]
+    fn restoring_carries_on_where_the_snapshot_left_off() {
+        let [first, second] = halves();
+
+        let mut whole = Engine::new();
+        run(&mut whole, &first);
+        // the second half comes later, as if it were the rows after the first
+        let offset = first.len() as u64;
+        let mut expected = vec![];
+        for (chronology, transaction) in second.iter().enumerate() {
+            let transaction = transaction.with_chronology(offset + chronology as u64);
+            expected.push(whole.apply(transaction).is_ok());
+        }
+
+        let mut before = Engine::new();
+        run(&mut before, &first);
+        let mut bytes = vec![];
+        before.snapshot(&mut bytes).unwrap();
+
+        let mut after = Engine::new();
+        after.restore(bytes.as_slice()).unwrap();
+        assert_eq!(run(&mut after, &second), expected);
+        assert_eq!(expected, [true, true, false, false, true]);
+
+        let snapshots = |engine: &Engine| {
+            engine
+                .clients()
+                .iter()
+                .map(|client| client.snapshot())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(snapshots(&after), snapshots(&whole));
+    }
+
+    #[test]
+    fn the_same_state_makes_the_same_bytes() {
+        let snapshot = || {
+            let mut engine = Engine::new();
+            run(&mut engine, &halves()[0]);
+            let mut bytes = vec![];
+            engine.snapshot(&mut bytes).unwrap();
+            bytes
+        };
+        // each engine's hash maps iterate in a different order
+        for _ in 0..10 {
+            assert_eq!(snapshot(), snapshot());
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_and_incompatible_snapshots() {
+        let mut engine = Engine::new();
+        run(&mut engine, &halves()[0]);
+        let mut bytes = vec![];
+        engine.snapshot(&mut bytes).unwrap();
+
+        let restore = |bytes: &[u8]| Engine::new().restore(bytes).unwrap_err().to_string();
+
+        let mut corrupted = bytes.clone();
+        corrupted[12] ^= 1;
+        assert!(restore(&corrupted).contains("checksum"));
+
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert!(restore(&newer).contains("version 2"));
+
+        assert!(restore(&bytes[..bytes.len() - 1]).contains("checksum"));
+        assert!(restore(b"accounts").contains("Not a snapshot"));
+
+        // only an engine which hasn't started can be restored into
+        assert!(engine.restore(bytes.as_slice()).is_err());
+    }
+}
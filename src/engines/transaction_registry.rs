@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use crate::transactions::TransactionType::*;
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+
+// transaction ids are globally unique, but each client only knows about its own transactions
+// this records which client every deposit and withdrawal belongs to
+// an id is taken as soon as it is seen, even if the client goes on to reject the transaction
+#[derive(Default, Debug)]
+pub struct TransactionRegistry {
+    // transaction id -> client id
+    owners: HashMap<u64, u64>,
+}
+
+impl TransactionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // transaction id -> client id, for every id taken so far
+    pub(crate) fn owners(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.owners
+            .iter()
+            .map(|(transaction_id, client_id)| (*transaction_id, *client_id))
+    }
+
+    pub(crate) fn from_owners(owners: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        Self {
+            owners: owners.into_iter().collect(),
+        }
+    }
+
+    // registers deposits and withdrawals, and checks claims reference a transaction owned by the same client
+    pub fn check(&mut self, transaction: &UnprocessedTransaction) -> Result<(), TransactionError> {
+        let transaction_id = transaction.metadata.transaction_id;
+        let client_id = transaction.metadata.client_id;
+
+        match transaction.transaction_type {
+            Deposit(_) | Withdrawal(_) => match self.owners.entry(transaction_id) {
+                Entry::Occupied(_) => Err(TransactionError::DuplicateTransactionId),
+                Entry::Vacant(entry) => {
+                    entry.insert(client_id);
+                    Ok(())
+                }
+            },
+            Claim(_) => match self.owners.get(&transaction_id) {
+                None => Err(TransactionError::UnknownTransaction),
+                Some(owner) if *owner != client_id => Err(TransactionError::ClientMismatch),
+                Some(_) => Ok(()),
+            },
+        }
+    }
+}
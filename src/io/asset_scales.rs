@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+
+use crate::io::SerializedAmount;
+use crate::transactions::{Amount, Asset};
+use crate::util::fixed::{format_decimal, parse_decimal};
+
+// the decimal places of any asset which isn't configured, which is what every amount had before they could be
+pub const DEFAULT_DECIMAL_PLACES: u32 = 4;
+// an asset with this many still has room for balances up to ~1.7e20
+pub const MAX_DECIMAL_PLACES: u32 = 18;
+
+// how many decimal places each asset's amounts are read and written with
+// an asset's amounts are held in units of its smallest decimal place, so each keeps the range it had at 4
+// e.g. 1.5 BTC at 8 decimal places is held as 150_000_000
+#[derive(Debug, Default, Clone)]
+pub struct AssetScales {
+    decimal_places: BTreeMap<Asset, u32>,
+}
+
+// a single asset's decimal places, written ASSET=N, e.g. BTC=8
+// an empty asset code configures the default asset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssetScale {
+    pub asset: Asset,
+    pub decimal_places: u32,
+}
+
+impl FromStr for AssetScale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (asset, decimal_places) = s
+            .split_once('=')
+            .ok_or(anyhow!("Invalid asset scale {s}, expected ASSET=N"))?;
+        let decimal_places = decimal_places
+            .parse::<u32>()
+            .map_err(|err| anyhow!("Invalid asset scale {s}: {err}"))?;
+        if decimal_places > MAX_DECIMAL_PLACES {
+            return Err(anyhow!(
+                "Invalid asset scale {s}, assets can have at most {MAX_DECIMAL_PLACES} decimal places"
+            ));
+        }
+
+        Ok(AssetScale {
+            asset: Asset::from_str(asset)?,
+            decimal_places,
+        })
+    }
+}
+
+impl FromIterator<AssetScale> for AssetScales {
+    // later scales for the same asset replace earlier ones
+    fn from_iter<I: IntoIterator<Item = AssetScale>>(scales: I) -> Self {
+        Self {
+            decimal_places: scales
+                .into_iter()
+                .map(|scale| (scale.asset, scale.decimal_places))
+                .collect(),
+        }
+    }
+}
+
+impl AssetScales {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decimal_places(&self, asset: Asset) -> u32 {
+        self.decimal_places
+            .get(&asset)
+            .copied()
+            .unwrap_or(DEFAULT_DECIMAL_PLACES)
+    }
+
+    // reads an amount in asset, failing if it has more decimal places than the asset
+    pub fn parse(&self, asset: Asset, s: &str) -> Result<Amount> {
+        parse_decimal(s, self.decimal_places(asset))
+            .map(Amount::from_raw)
+            .map_err(|err| anyhow!("Invalid amount in {}: {err}", describe(asset)))
+    }
+
+    // converts an amount read before its row's asset was known, failing if it has more decimal places than the asset
+    pub(crate) fn scale(&self, asset: Asset, amount: SerializedAmount) -> Result<Amount> {
+        let decimal_places = self.decimal_places(asset);
+        if amount.decimal_places > decimal_places {
+            return Err(anyhow!(
+                "Amount in {} has more than {decimal_places} decimal places",
+                describe(asset)
+            ));
+        }
+
+        10i128
+            .checked_pow(decimal_places - amount.decimal_places)
+            .and_then(|factor| amount.raw.checked_mul(factor))
+            .map(Amount::from_raw)
+            .ok_or_else(|| anyhow!("Amount in {} is too large", describe(asset)))
+    }
+
+    // writes an amount in asset with all of the asset's decimal places
+    // the amount is already a whole number of them, so there's nothing which can't be written
+    pub fn format(&self, asset: Asset, amount: Amount) -> String {
+        format_decimal(amount.raw(), self.decimal_places(asset))
+    }
+}
+
+fn describe(asset: Asset) -> String {
+    if asset.is_default() {
+        "the default asset".to_string()
+    } else {
+        asset.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scales(scales: &[&str]) -> AssetScales {
+        scales.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn configured_assets_have_their_own_decimal_places() {
+        let scales = scales(&["BTC=8", "ETH=18", "=2"]);
+        let btc = Asset::from_str("BTC").unwrap();
+
+        let satoshi = scales.parse(btc, "0.00000001").unwrap();
+        assert_eq!(satoshi.raw(), 1);
+        assert_eq!(scales.format(btc, satoshi), "0.00000001");
+        // anything not configured has the default decimal places
+        let usd = Asset::from_str("USD").unwrap();
+        assert_eq!(
+            scales.format(usd, scales.parse(usd, "1.5").unwrap()),
+            "1.5000"
+        );
+        assert_eq!(
+            scales.format(usd, Amount::from_str("1.5").unwrap()),
+            "1.5000"
+        );
+
+        let err = scales.parse(Asset::default(), "1.005").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid amount in the default asset: Fixed point value has more than 2 decimal places: 1.005"
+        );
+        // each asset has the range the default asset has always had
+        let large = "10000000000000000000000000.0000";
+        assert_eq!(scales.format(usd, scales.parse(usd, large).unwrap()), large);
+    }
+
+    #[test]
+    fn scales_amounts_to_their_asset() {
+        let scales = scales(&["BTC=8"]);
+        let btc = Asset::from_str("BTC").unwrap();
+        let amount = |raw, decimal_places| SerializedAmount {
+            raw,
+            decimal_places,
+        };
+
+        assert_eq!(scales.scale(btc, amount(15, 1)).unwrap().raw(), 150_000_000);
+        assert_eq!(scales.scale(btc, amount(1, 8)).unwrap().raw(), 1);
+        let err = scales.scale(Asset::default(), amount(1, 8)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Amount in the default asset has more than 4 decimal places"
+        );
+        let err = scales.scale(btc, amount(i128::MAX / 10, 0)).unwrap_err();
+        assert_eq!(err.to_string(), "Amount in BTC is too large");
+    }
+
+    #[test]
+    fn parses_asset_scales() {
+        let scale: AssetScale = "BTC=8".parse().unwrap();
+        assert_eq!(scale.asset, Asset::from_str("BTC").unwrap());
+        assert_eq!(scale.decimal_places, 8);
+        assert!("=4".parse::<AssetScale>().unwrap().asset.is_default());
+        for s in ["BTC", "BTC=19", "BTC=-1", "B!C=2"] {
+            assert!(s.parse::<AssetScale>().is_err(), "{s} should not parse");
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use arrow_array::{ArrayRef, BooleanArray, Decimal128Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+
+use crate::clients::Client;
+use crate::clients::client::ClientState;
+use crate::io::AssetScales;
+use crate::io::asset_scales::DEFAULT_DECIMAL_PLACES;
+use crate::io::serialized_client::{AccountsLayout, includes_asset, wide_header};
+use crate::transactions::{Amount, Asset};
+
+// the most digits a parquet decimal backed by an i128 can declare
+const DECIMAL_PRECISION: u8 = 38;
+
+// the same columns as the csv output, in a single row group
+// amounts are decimals built from Amount's raw value, which is already in units of its asset's decimal places
+pub fn write_clients_parquet<W: Write + Send>(
+    writer: W,
+    clients: &[&Client],
+    assets: &BTreeSet<Asset>,
+    layout: AccountsLayout,
+    scales: &AssetScales,
+) -> Result<()> {
+    let mut columns: Vec<(Field, ArrayRef)> = vec![];
+    let balances: [fn(&ClientState) -> Amount; 3] = [
+        ClientState::available_funds,
+        ClientState::held_funds,
+        ClientState::total_funds,
+    ];
+
+    match layout {
+        AccountsLayout::Long => {
+            // one row per (client, asset), the same as the csv
+            let rows: Vec<(&Client, Asset, ClientState)> = clients
+                .iter()
+                .flat_map(|client| {
+                    client
+                        .states()
+                        .map(move |(asset, state)| (*client, asset, state))
+                })
+                .collect();
+            columns.push(client_column(rows.iter().map(|(client, _, _)| client.id())));
+            if includes_asset(assets) {
+                let assets = rows.iter().map(|(_, asset, _)| asset.to_string());
+                columns.push((
+                    Field::new("asset", DataType::Utf8, false),
+                    Arc::new(StringArray::from_iter_values(assets)),
+                ));
+            }
+            // a column has a single scale, so it's the most decimal places of any of the assets
+            let decimal_places = assets
+                .iter()
+                .map(|asset| scales.decimal_places(*asset))
+                .max()
+                .unwrap_or(DEFAULT_DECIMAL_PLACES);
+            for (name, balance) in ["available", "held", "total"].into_iter().zip(balances) {
+                let values = rows
+                    .iter()
+                    .map(|(_, asset, state)| (balance(state), scales.decimal_places(*asset)));
+                columns.push(decimal_column(name, values, decimal_places)?);
+            }
+            columns.push(locked_column(
+                rows.iter().map(|(client, _, _)| client.is_locked()),
+            ));
+        }
+        AccountsLayout::Wide => {
+            columns.push(client_column(clients.iter().map(|client| client.id())));
+            // the header has available, held and total for each asset between client and locked
+            let header = wide_header(assets);
+            let names = header[1..header.len() - 1].iter();
+            let sources = assets
+                .iter()
+                .flat_map(|asset| balances.map(|balance| (asset, balance)));
+            for (name, (asset, balance)) in names.zip(sources) {
+                let decimal_places = scales.decimal_places(*asset);
+                let values = clients
+                    .iter()
+                    .map(|client| (balance(&client.state(*asset)), decimal_places));
+                columns.push(decimal_column(name, values, decimal_places)?);
+            }
+            columns.push(locked_column(
+                clients.iter().map(|client| client.is_locked()),
+            ));
+        }
+    }
+
+    let (fields, arrays): (Vec<Field>, Vec<ArrayRef>) = columns.into_iter().unzip();
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+    let mut writer = ArrowWriter::try_new(writer, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+fn client_column(client_ids: impl Iterator<Item = u64>) -> (Field, ArrayRef) {
+    (
+        Field::new("client", DataType::UInt64, false),
+        Arc::new(UInt64Array::from_iter_values(client_ids)),
+    )
+}
+
+// values are amounts with their asset's decimal places, which can't be more than the column's
+fn decimal_column(
+    name: &str,
+    values: impl Iterator<Item = (Amount, u32)>,
+    decimal_places: u32,
+) -> Result<(Field, ArrayRef)> {
+    let scale = decimal_places as i8;
+    let raw = values
+        .map(|(value, value_decimal_places)| {
+            10i128
+                .checked_pow(decimal_places - value_decimal_places)
+                .and_then(|factor| value.raw().checked_mul(factor))
+                .ok_or_else(|| {
+                    anyhow!("Balance too large for a column with {decimal_places} decimal places, try --layout wide")
+                })
+        })
+        .collect::<Result<Vec<i128>>>()?;
+    let array = Decimal128Array::from_iter_values(raw)
+        .with_precision_and_scale(DECIMAL_PRECISION, scale)?;
+
+    Ok((
+        Field::new(name, DataType::Decimal128(DECIMAL_PRECISION, scale), false),
+        Arc::new(array),
+    ))
+}
+
+fn locked_column(locked: impl Iterator<Item = bool>) -> (Field, ArrayRef) {
+    (
+        Field::new("locked", DataType::Boolean, false),
+        Arc::new(BooleanArray::from_iter(locked.map(Some))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::Engine;
+    use crate::transactions::UnprocessedTransaction;
+    use arrow_array::Array;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Decimal128Type;
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::str::FromStr;
+
+    fn read_back(clients: &[&Client], layout: AccountsLayout) -> RecordBatch {
+        let assets = clients
+            .iter()
+            .flat_map(|client| client.states().map(|(asset, _)| asset))
+            .collect();
+        let scales = ["BTC=8".parse().unwrap()].into_iter().collect();
+        let mut bytes = vec![];
+        write_clients_parquet(&mut bytes, clients, &assets, layout, &scales).unwrap();
+
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(bytes))
+            .unwrap()
+            .build()
+            .unwrap();
+        reader.next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn amounts_are_exact_decimals() {
+        let amount = |s| Amount::from_str(s).unwrap();
+        let btc = Asset::from_str("BTC").unwrap();
+        let mut engine = Engine::new();
+        let transactions = [
+            UnprocessedTransaction::deposit(1, 1, amount("1.2345")),
+            // in BTC's smallest unit, 99999999999.99999999 BTC
+            UnprocessedTransaction::deposit(2, 2, Amount::from_raw(9_999_999_999_999_999_999))
+                .with_asset(btc),
+            UnprocessedTransaction::dispute(2, 2),
+        ];
+        for (chronology, transaction) in transactions.into_iter().enumerate() {
+            engine
+                .apply(transaction.with_chronology(chronology as u64))
+                .unwrap();
+        }
+
+        let batch = read_back(&engine.clients(), AccountsLayout::Long);
+        let names: Vec<&str> = batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["client", "asset", "available", "held", "total", "locked"]
+        );
+        // BTC has the most decimal places, so every asset is written with them
+        assert_eq!(
+            batch.column(2).data_type(),
+            &DataType::Decimal128(DECIMAL_PRECISION, 8)
+        );
+        let available = batch.column(2).as_primitive::<Decimal128Type>();
+        let held = batch.column(3).as_primitive::<Decimal128Type>();
+        assert_eq!(available.value(0), 123_450_000);
+        assert_eq!(held.value(1), 9_999_999_999_999_999_999);
+
+        let batch = read_back(&engine.clients(), AccountsLayout::Wide);
+        let names: Vec<&str> = batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "client",
+                "available",
+                "held",
+                "total",
+                "BTC_available",
+                "BTC_held",
+                "BTC_total",
+                "locked"
+            ]
+        );
+        assert_eq!(
+            batch.column(1).data_type(),
+            &DataType::Decimal128(DECIMAL_PRECISION, 4)
+        );
+        let btc_total = batch.column(6).as_primitive::<Decimal128Type>();
+        assert_eq!(btc_total.value(1), 9_999_999_999_999_999_999);
+        assert_eq!(batch.column(7).null_count(), 0);
+    }
+}
@@ -0,0 +1,59 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+// how transactions are encoded in an input
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    // a header row, then one transaction per row
+    #[default]
+    Csv,
+    // one json object per line, amounts as strings
+    #[value(alias = "ndjson")]
+    Jsonl,
+    // a binary transaction log, written by the convert command
+    Bin,
+}
+
+impl InputFormat {
+    // anything without a json lines or binary log extension is read as csv, including stdin
+    pub fn from_filepath(filepath: &str) -> Self {
+        match extension(filepath).as_deref() {
+            Some("jsonl" | "ndjson") => InputFormat::Jsonl,
+            Some("bin") => InputFormat::Bin,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+// how accounts are encoded in the output
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    // a header row, then one row per account
+    #[default]
+    Csv,
+    // a single array of objects
+    Json,
+    // one object per line
+    #[value(alias = "jsonl")]
+    Ndjson,
+    // a parquet file with amounts as decimal(38, 4), only available with the parquet feature
+    Parquet,
+}
+
+impl OutputFormat {
+    // anything without a json or parquet extension is written as csv
+    pub fn from_filepath(filepath: &str) -> Self {
+        match extension(filepath).as_deref() {
+            Some("json") => OutputFormat::Json,
+            Some("jsonl" | "ndjson") => OutputFormat::Ndjson,
+            Some("parquet") => OutputFormat::Parquet,
+            _ => OutputFormat::Csv,
+        }
+    }
+}
+
+fn extension(filepath: &str) -> Option<String> {
+    Path::new(filepath)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+}
@@ -0,0 +1,153 @@
+use std::iter::Peekable;
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::io::transaction_log::read_transactions_from_log;
+use crate::io::transactions_csv::{
+    read_transactions_from_csv, read_transactions_from_csv_on_threads,
+};
+use crate::io::transactions_json::read_transactions_from_json_lines;
+use crate::io::{AssetScales, InputFormat, ParseError};
+use crate::transactions::UnprocessedTransaction;
+
+pub const STDIN_FILEPATH: &str = "-";
+
+// rows are handed from reader threads in batches to keep channel overhead down
+const BATCH_SIZE: usize = 1024;
+// how many batches a reader can get ahead of the merge before it blocks, bounding memory
+const CHANNEL_CAPACITY: usize = 16;
+
+type ParsedRow = Result<UnprocessedTransaction, ParseError>;
+
+// a filepath of - reads from stdin
+// without a format, it's picked by the file's extension
+// amounts in binary logs are already scaled, so scales only apply to text formats
+// with more than one parser, csv rows are deserialized on that many threads
+pub fn read_transactions_from_file(
+    filepath: &str,
+    format: Option<InputFormat>,
+    source: u32,
+    scales: &AssetScales,
+    parsers: usize,
+) -> Result<Box<dyn Iterator<Item = ParsedRow> + Send>> {
+    let format = format.unwrap_or_else(|| InputFormat::from_filepath(filepath));
+    // binary logs are memory mapped rather than read
+    if format == InputFormat::Bin {
+        return Ok(Box::new(read_transactions_from_log(filepath, source)?));
+    }
+
+    let reader: Box<dyn Read + Send> = if filepath == STDIN_FILEPATH {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(filepath).with_context(|| format!("Failed to open {filepath}"))?)
+    };
+    let reader = BufReader::new(reader);
+
+    Ok(match format {
+        InputFormat::Csv if parsers > 1 => Box::new(read_transactions_from_csv_on_threads(
+            reader,
+            source,
+            scales.clone(),
+            parsers,
+        )),
+        InputFormat::Csv => Box::new(read_transactions_from_csv(reader, source, scales.clone())),
+        InputFormat::Jsonl => Box::new(read_transactions_from_json_lines(
+            reader,
+            source,
+            scales.clone(),
+        )),
+        InputFormat::Bin => unreachable!("Binary logs are read above"),
+    })
+}
+
+// reads every input, each on its own thread, and merges them into a single stream in chronology order
+// each input is expected to be in order already (or close enough for the reorder window to fix)
+// ties are broken by input order, so the result doesn't depend on how fast each input is read
+// a single input is read on the calling thread, the same as before there could be several
+pub fn read_transactions_from_files(
+    filepaths: &[String],
+    format: Option<InputFormat>,
+    scales: &AssetScales,
+    parsers: usize,
+) -> Result<Box<dyn Iterator<Item = ParsedRow>>> {
+    if filepaths.iter().filter(|f| *f == STDIN_FILEPATH).count() > 1 {
+        return Err(anyhow!("Stdin can only be read once"));
+    }
+
+    if let [filepath] = filepaths {
+        return read_transactions_from_file(filepath, format, 0, scales, parsers)
+            .map(|rows| rows as Box<dyn Iterator<Item = ParsedRow>>);
+    }
+
+    // open everything up front so a missing file is reported before any rows are processed
+    let readers = filepaths
+        .iter()
+        .enumerate()
+        .map(|(source, filepath)| {
+            read_transactions_from_file(filepath, format, source as u32, scales, parsers)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Box::new(MergedSources {
+        sources: readers
+            .into_iter()
+            .map(|reader| read_on_thread(reader).peekable())
+            .collect(),
+    }))
+}
+
+fn read_on_thread(
+    rows: impl Iterator<Item = ParsedRow> + Send + 'static,
+) -> impl Iterator<Item = ParsedRow> {
+    let (sender, receiver) = sync_channel::<Vec<ParsedRow>>(CHANNEL_CAPACITY);
+    thread::spawn(move || {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for row in rows {
+            batch.push(row);
+            if batch.len() >= BATCH_SIZE {
+                let full_batch = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                // the merge has been dropped, nothing left to read for
+                if sender.send(full_batch).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = sender.send(batch);
+    });
+
+    receiver.into_iter().flatten()
+}
+
+struct MergedSources<I: Iterator<Item = ParsedRow>> {
+    sources: Vec<Peekable<I>>,
+}
+
+impl<I: Iterator<Item = ParsedRow>> Iterator for MergedSources<I> {
+    type Item = ParsedRow;
+
+    // waits for the next row of every input which hasn't finished
+    fn next(&mut self) -> Option<ParsedRow> {
+        let mut earliest: Option<(u64, usize)> = None;
+        for (index, source) in self.sources.iter_mut().enumerate() {
+            match source.peek() {
+                None => {}
+                // bad rows have no chronology, report them as soon as they're reached
+                Some(Err(_)) => return source.next(),
+                Some(Ok(transaction)) => {
+                    let key = (transaction.metadata.chronology, index);
+                    if earliest.is_none_or(|earliest| key < earliest) {
+                        earliest = Some(key);
+                    }
+                }
+            }
+        }
+
+        let (_, index) = earliest?;
+        self.sources[index].next()
+    }
+}
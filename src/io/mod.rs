@@ -1,5 +1,24 @@
+mod serialized_transaction;
 mod serialized_transaction_type;
 pub mod transactions_csv;
+pub mod transactions_json;
+use serialized_transaction::{SerializedAmount, SerializedTransaction};
 use serialized_transaction_type::SerializedTransactionType;
 
+pub mod format;
+pub use format::{InputFormat, OutputFormat};
+
+pub mod parse_error;
+pub use parse_error::{ParseError, ParseErrorPolicy};
+
+pub mod asset_scales;
+pub use asset_scales::AssetScales;
+
+#[cfg(feature = "parquet")]
+pub mod clients_parquet;
+pub mod merged_sources;
+pub mod opening_balances_csv;
+pub mod rejections_csv;
 pub mod serialized_client;
+pub mod transaction_log;
+pub mod write_ahead_log;
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use num::CheckedAdd;
+
+use crate::clients::ClientSnapshot;
+use crate::clients::client::ClientState;
+use crate::io::AssetScales;
+use crate::io::serialized_client::SerializedClient;
+use crate::transactions::Asset;
+
+pub fn read_opening_balances_from_file(
+    filepath: &str,
+    scales: &AssetScales,
+) -> Result<Vec<ClientSnapshot>> {
+    let file = File::open(filepath).with_context(|| format!("Failed to open {filepath}"))?;
+    read_opening_balances(BufReader::new(file), scales)
+        .with_context(|| format!("Invalid opening balances in {filepath}"))
+}
+
+// reads accounts in the long layout, as written by a previous run, ordered by client id
+// rows without an asset column are in the default asset, a client can have a row per asset
+// balances can't have more decimal places than their asset, the same as transaction amounts
+// unlike transactions, a bad row fails the whole file, starting from the wrong balances isn't recoverable
+pub fn read_opening_balances<R: Read>(
+    reader: R,
+    scales: &AssetScales,
+) -> Result<Vec<ClientSnapshot>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    // the wide layout would otherwise read as just its default asset
+    if reader
+        .headers()?
+        .iter()
+        .any(|column| column.ends_with("_available"))
+    {
+        return Err(anyhow!(
+            "Only the long layout can be read, write the accounts with --layout long"
+        ));
+    }
+
+    let mut clients: BTreeMap<u64, ClientSnapshot> = BTreeMap::new();
+    for (row, result) in reader.deserialize().enumerate() {
+        let account: SerializedClient = result.with_context(|| format!("Row {row}"))?;
+        let asset = match account.asset.as_deref() {
+            Some(asset) => Asset::from_str(asset).with_context(|| format!("Row {row}"))?,
+            None => Asset::default(),
+        };
+        let parse = |balance: &str| {
+            scales
+                .parse(asset, balance)
+                .with_context(|| format!("Row {row}"))
+        };
+        let available_funds = parse(&account.available_funds)?;
+        let held_funds = parse(&account.held_funds)?;
+        let total_funds = parse(&account.total_funds)?;
+
+        let total = available_funds
+            .checked_add(&held_funds)
+            .filter(|total| *total == total_funds);
+        if total.is_none() {
+            return Err(anyhow!(
+                "Row {row}: total {} is not available {} + held {}",
+                account.total_funds,
+                account.available_funds,
+                account.held_funds
+            ));
+        }
+
+        let client = clients
+            .entry(account.client_id)
+            .or_insert_with(|| ClientSnapshot {
+                id: account.client_id,
+                states: BTreeMap::new(),
+                locked: account.locked,
+            });
+        // locking applies to every asset, so each of the client's rows should agree
+        if client.locked != account.locked {
+            return Err(anyhow!(
+                "Row {row}: client {} is both locked and unlocked",
+                account.client_id
+            ));
+        }
+        match client.states.entry(asset) {
+            Entry::Occupied(_) => {
+                return Err(anyhow!(
+                    "Row {row}: client {} already has a balance in {}",
+                    account.client_id,
+                    if asset.is_default() {
+                        "the default asset"
+                    } else {
+                        asset.as_str()
+                    }
+                ));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(ClientState::new(available_funds, held_funds));
+            }
+        }
+    }
+
+    Ok(clients.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::Amount;
+
+    fn read(csv: &str) -> Result<Vec<ClientSnapshot>> {
+        let scales = ["BTC=8".parse().unwrap()].into_iter().collect();
+        read_opening_balances(csv.as_bytes(), &scales)
+    }
+
+    #[test]
+    fn groups_assets_by_client() {
+        let clients = read(
+            "client,asset,available,held,total,locked
+             2,BTC,1.00000001,0,1.00000001,true
+             1,,-2,3,1,false
+             2,,0,0,0,true",
+        )
+        .unwrap();
+
+        let fixed = |s| Amount::from_str(s).unwrap();
+        assert_eq!(clients.iter().map(|c| c.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(
+            clients[0].states[&Asset::default()],
+            ClientState::new(fixed("-2"), fixed("3"))
+        );
+        assert_eq!(clients[1].states.len(), 2);
+        assert!(clients[1].locked);
+    }
+
+    #[test]
+    fn rejects_inconsistent_balances() {
+        let header = "client,asset,available,held,total,locked\n";
+        for (rows, reason) in [
+            ("1,,1,1,3,false", "is not available"),
+            ("1,,1,0,1,false\n1,,2,0,2,false", "already has a balance"),
+            (
+                "1,,1,0,1,false\n1,BTC,2,0,2,true",
+                "both locked and unlocked",
+            ),
+            ("1,,1,0,1,maybe", "Row 0"),
+            ("1,,0.00001,0,0.00001,false", "more than 4 decimal places"),
+        ] {
+            let err = read(&format!("{header}{rows}")).unwrap_err();
+            assert!(format!("{err:#}").contains(reason), "{err:#}");
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::str::FromStr;
+
+// a row of input which could not be turned into a transaction
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    // matches the source and row number transactions are given
+    pub source: u32,
+    pub row: u64,
+    pub reason: String,
+}
+
+impl ParseError {
+    pub fn new(source: u32, row: u64, reason: String) -> Self {
+        Self {
+            source,
+            row,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid transaction on row {}: {}",
+            self.row, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// what to do when a row fails to parse
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ParseErrorPolicy {
+    // abort on the first bad row
+    #[default]
+    Strict,
+    // drop every bad row
+    Skip,
+    // drop up to N bad rows, aborting on the next one
+    SkipWithLimit(usize),
+}
+
+impl ParseErrorPolicy {
+    // whether processing can continue having seen error_count bad rows so far
+    pub fn tolerates(&self, error_count: usize) -> bool {
+        match self {
+            ParseErrorPolicy::Strict => false,
+            ParseErrorPolicy::Skip => true,
+            ParseErrorPolicy::SkipWithLimit(limit) => error_count <= *limit,
+        }
+    }
+}
+
+impl FromStr for ParseErrorPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "strict" => Ok(ParseErrorPolicy::Strict),
+            "skip" => Ok(ParseErrorPolicy::Skip),
+            _ => {
+                let limit = s
+                    .strip_prefix("skip-with-limit=")
+                    .ok_or(anyhow!(
+                        "Invalid parse error policy {s}, expected strict, skip or skip-with-limit=N"
+                    ))?
+                    .parse::<usize>()
+                    .map_err(|err| anyhow!("Invalid parse error limit in {s}: {err}"))?;
+                Ok(ParseErrorPolicy::SkipWithLimit(limit))
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::io::{ParseError, SerializedTransactionType};
+use crate::transactions::{TransactionError, UnprocessedTransaction};
+use anyhow::Result;
+use csv::Writer;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+
+#[derive(Debug, Serialize)]
+struct CsvRejection {
+    // the input the row came from, only written when reading multiple inputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    // the input row number
+    pub row: u64,
+    // the following are empty for rows which failed to parse
+    #[serde(rename(serialize = "client"))]
+    pub client_id: Option<u64>,
+    #[serde(rename(serialize = "tx"))]
+    pub transaction_id: Option<u64>,
+    #[serde(rename(serialize = "type"))]
+    pub type_name: Option<SerializedTransactionType>,
+    pub reason: String,
+}
+
+// writes one row per rejected transaction, in the order they were rejected
+pub struct RejectionWriter {
+    writer: Writer<BufWriter<File>>,
+    source_names: Vec<String>,
+}
+
+impl RejectionWriter {
+    pub fn create(filepath: &str) -> Result<Self> {
+        let file = File::create(filepath)?;
+        Ok(Self {
+            writer: Writer::from_writer(BufWriter::new(file)),
+            source_names: vec![],
+        })
+    }
+
+    // names each input by its index, which adds a source column to the report
+    pub fn with_source_names(mut self, source_names: Vec<String>) -> Self {
+        self.source_names = source_names;
+        self
+    }
+
+    // names the next input, for inputs which appear while running
+    pub fn add_source_name(&mut self, source_name: String) {
+        self.source_names.push(source_name);
+    }
+
+    fn source_name(&self, source: u32) -> Option<String> {
+        self.source_names.get(source as usize).cloned()
+    }
+
+    pub fn write(
+        &mut self,
+        transaction: &UnprocessedTransaction,
+        error: &TransactionError,
+    ) -> Result<()> {
+        self.writer.serialize(CsvRejection {
+            source: self.source_name(transaction.metadata.source),
+            row: transaction.metadata.row,
+            client_id: Some(transaction.metadata.client_id),
+            transaction_id: Some(transaction.metadata.transaction_id),
+            type_name: Some((&transaction.transaction_type).into()),
+            reason: error.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    pub fn write_parse_error(&mut self, error: &ParseError) -> Result<()> {
+        self.writer.serialize(CsvRejection {
+            source: self.source_name(error.source),
+            row: error.row,
+            client_id: None,
+            transaction_id: None,
+            type_name: None,
+            reason: error.reason.clone(),
+        })?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
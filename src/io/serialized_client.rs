@@ -1,53 +1,261 @@
 use crate::clients::Client;
-use crate::util::Fixed;
+use crate::clients::client::ClientState;
+#[cfg(feature = "parquet")]
+use crate::io::clients_parquet::write_clients_parquet;
+use crate::io::{AssetScales, OutputFormat};
+use crate::transactions::Asset;
 use anyhow::Result;
+#[cfg(not(feature = "parquet"))]
+use anyhow::anyhow;
+use clap::ValueEnum;
 use csv::Writer;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fs::File;
 use std::io;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 
-#[derive(Debug, Serialize)]
-struct CsvClient {
-    #[serde(rename(serialize = "client"))]
-    pub client_id: u64,
-    #[serde(rename(serialize = "available"))]
-    #[serde(serialize_with = "se_fixed")]
-    pub available_funds: Fixed,
-    #[serde(rename(serialize = "held"))]
-    #[serde(serialize_with = "se_fixed")]
-    pub held_funds: Fixed,
-    #[serde(rename(serialize = "total"))]
-    #[serde(serialize_with = "se_fixed")]
-    pub total_funds: Fixed,
-    pub locked: bool,
+// how balances in multiple assets are laid out
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum AccountsLayout {
+    // one row per (client, asset)
+    // the asset column is only written if any balance is in a non-default asset
+    #[default]
+    Long,
+    // one row per client, with available/held/total columns per asset
+    // columns for the default asset are unprefixed, other assets are prefixed with the asset code e.g. BTC_available
+    Wide,
 }
 
-fn se_fixed<S>(fixed: &Fixed, se: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    String::serialize(&fixed.to_string(), se)
+// one account in the long layout, the same fields whether written as csv or json
+// also read back as opening balances
+// amounts are always strings with their asset's decimal places, so json readers don't round them through a float
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SerializedClient {
+    #[serde(rename = "client")]
+    pub client_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub asset: Option<String>,
+    #[serde(rename = "available")]
+    pub available_funds: String,
+    #[serde(rename = "held")]
+    pub held_funds: String,
+    #[serde(rename = "total")]
+    pub total_funds: String,
+    pub locked: bool,
 }
 
-impl From<Client> for CsvClient {
-    fn from(client: Client) -> Self {
+impl SerializedClient {
+    fn new(
+        client: &Client,
+        asset: Asset,
+        include_asset: bool,
+        state: ClientState,
+        scales: &AssetScales,
+    ) -> Self {
         Self {
             client_id: client.id(),
-            available_funds: client.available_funds(),
-            held_funds: client.held_funds(),
-            total_funds: client.total_funds(),
+            asset: include_asset.then(|| asset.to_string()),
+            available_funds: scales.format(asset, state.available_funds()),
+            held_funds: scales.format(asset, state.held_funds()),
+            total_funds: scales.format(asset, state.total_funds()),
             locked: client.is_locked(),
         }
     }
 }
 
-pub fn write_clients_to_stdout(clients: &[Option<Client>]) -> Result<()> {
-    let buf_writer = BufWriter::new(io::stdout());
-    let mut writer = Writer::from_writer(buf_writer);
-    for client in clients.iter().flatten() {
-        let csv_client: CsvClient = client.clone().into();
-        writer.serialize(csv_client)?;
+pub fn write_clients_to_stdout(
+    clients: &[&Client],
+    layout: AccountsLayout,
+    format: OutputFormat,
+    scales: &AssetScales,
+) -> Result<()> {
+    write_clients(
+        BufWriter::new(io::stdout()),
+        clients,
+        layout,
+        format,
+        scales,
+    )
+}
+
+// without a format, it's picked by the file's extension
+pub fn write_clients_to_file(
+    filepath: &str,
+    clients: &[&Client],
+    layout: AccountsLayout,
+    format: Option<OutputFormat>,
+    scales: &AssetScales,
+) -> Result<()> {
+    let format = format.unwrap_or_else(|| OutputFormat::from_filepath(filepath));
+    write_clients(
+        BufWriter::new(File::create(filepath)?),
+        clients,
+        layout,
+        format,
+        scales,
+    )
+}
+
+// clients are written in the order given, with each asset's amounts at its decimal places
+pub fn write_clients<W: Write + Send>(
+    writer: W,
+    clients: &[&Client],
+    layout: AccountsLayout,
+    format: OutputFormat,
+    scales: &AssetScales,
+) -> Result<()> {
+    // every asset held by any client, ordered by asset
+    let assets: BTreeSet<Asset> = clients
+        .iter()
+        .flat_map(|client| client.states().map(|(asset, _)| asset))
+        .collect();
+
+    match format {
+        OutputFormat::Csv => write_csv(writer, clients, &assets, layout, scales),
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            write_json(writer, clients, &assets, layout, format, scales)
+        }
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => write_clients_parquet(writer, clients, &assets, layout, scales),
+        #[cfg(not(feature = "parquet"))]
+        OutputFormat::Parquet => Err(anyhow!(
+            "Parquet output needs the parquet feature, build with --features parquet"
+        )),
     }
+}
+
+fn write_csv<W: Write>(
+    writer: W,
+    clients: &[&Client],
+    assets: &BTreeSet<Asset>,
+    layout: AccountsLayout,
+    scales: &AssetScales,
+) -> Result<()> {
+    let mut writer = Writer::from_writer(writer);
+
+    match layout {
+        AccountsLayout::Long => {
+            for client in long_clients(clients, assets, scales) {
+                writer.serialize(client)?;
+            }
+        }
+        AccountsLayout::Wide => {
+            writer.write_record(wide_header(assets))?;
+            for client in clients {
+                let mut record = vec![client.id().to_string()];
+                record.extend(wide_balances(client, assets, scales));
+                record.push(client.is_locked().to_string());
+                writer.write_record(&record)?;
+            }
+        }
+    }
+
+    writer.flush()?;
 
     Ok(())
 }
+
+// objects have the same keys as the csv columns
+fn write_json<W: Write>(
+    mut writer: W,
+    clients: &[&Client],
+    assets: &BTreeSet<Asset>,
+    layout: AccountsLayout,
+    format: OutputFormat,
+    scales: &AssetScales,
+) -> Result<()> {
+    let values: Box<dyn Iterator<Item = Value>> = match layout {
+        AccountsLayout::Long => Box::new(
+            long_clients(clients, assets, scales)
+                .map(|client| serde_json::to_value(client).expect("Accounts are valid json")),
+        ),
+        AccountsLayout::Wide => {
+            let header = wide_header(assets);
+            Box::new(clients.iter().map(move |client| {
+                let mut object = Map::new();
+                object.insert(header[0].clone(), client.id().into());
+                for (column, balance) in header[1..]
+                    .iter()
+                    .zip(wide_balances(client, assets, scales))
+                {
+                    object.insert(column.clone(), balance.into());
+                }
+                object.insert("locked".to_string(), client.is_locked().into());
+                Value::Object(object)
+            }))
+        }
+    };
+
+    if format == OutputFormat::Ndjson {
+        for value in values {
+            serde_json::to_writer(&mut writer, &value)?;
+            writeln!(writer)?;
+        }
+    } else {
+        // one object per line inside the array, so it still diffs well
+        write!(writer, "[")?;
+        for (i, value) in values.enumerate() {
+            writeln!(writer, "{}", if i == 0 { "" } else { "," })?;
+            serde_json::to_writer(&mut writer, &value)?;
+        }
+        writeln!(writer, "\n]")?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+// one row per (client, asset)
+// the asset is only included if any balance is in a non-default asset
+fn long_clients<'a>(
+    clients: &'a [&Client],
+    assets: &BTreeSet<Asset>,
+    scales: &'a AssetScales,
+) -> impl Iterator<Item = SerializedClient> + 'a {
+    let include_asset = includes_asset(assets);
+    clients.iter().flat_map(move |client| {
+        client.states().map(move |(asset, state)| {
+            SerializedClient::new(client, asset, include_asset, state, scales)
+        })
+    })
+}
+
+// whether the long layout has an asset column
+pub(crate) fn includes_asset(assets: &BTreeSet<Asset>) -> bool {
+    assets.iter().any(|asset| !asset.is_default())
+}
+
+pub(crate) fn wide_header(assets: &BTreeSet<Asset>) -> Vec<String> {
+    let mut header = vec!["client".to_string()];
+    for asset in assets {
+        for column in ["available", "held", "total"] {
+            header.push(if asset.is_default() {
+                column.to_string()
+            } else {
+                format!("{asset}_{column}")
+            });
+        }
+    }
+    header.push("locked".to_string());
+    header
+}
+
+// available, held and total for every asset, in the same order as the header
+fn wide_balances(client: &Client, assets: &BTreeSet<Asset>, scales: &AssetScales) -> Vec<String> {
+    assets
+        .iter()
+        .flat_map(|asset| {
+            let state = client.state(*asset);
+            [
+                state.available_funds(),
+                state.held_funds(),
+                state.total_funds(),
+            ]
+            .map(|balance| scales.format(*asset, balance))
+        })
+        .collect()
+}
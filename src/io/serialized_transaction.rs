@@ -0,0 +1,139 @@
+use crate::io::asset_scales::MAX_DECIMAL_PLACES;
+use crate::io::{AssetScales, ParseError, SerializedTransactionType};
+use crate::transactions::transaction::ClaimType;
+use crate::transactions::{Asset, TransactionType, UnprocessedTransaction};
+use crate::util::fixed::parse_decimal;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Deserializer, de};
+use std::fmt;
+use std::str::FromStr;
+
+// a transaction as it appears in an input file, before it is validated
+#[derive(Debug, Deserialize)]
+pub struct SerializedTransaction {
+    #[serde(rename(deserialize = "type"))]
+    pub type_name: SerializedTransactionType,
+    #[serde(rename(deserialize = "client"))]
+    pub client_id: u64,
+    #[serde(rename(deserialize = "tx"))]
+    pub transaction_id: u64,
+    #[serde(rename(deserialize = "amount"))]
+    #[serde(deserialize_with = "de_fixed")]
+    #[serde(default)]
+    pub amount: Option<SerializedAmount>,
+    // optional column, rows without an asset use the default asset
+    #[serde(alias = "currency")]
+    #[serde(deserialize_with = "de_asset")]
+    #[serde(default)]
+    pub asset: Asset,
+    // optional column giving the chronology, rows without one use their row number
+    #[serde(rename(deserialize = "timestamp"))]
+    #[serde(alias = "seq")]
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    #[serde(skip)]
+    pub row: u64,
+}
+
+// an amount as it was written, in units of however many decimal places it was written with
+// the row's asset isn't known until the whole row has been read, see AssetScales::scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerializedAmount {
+    pub raw: i128,
+    pub decimal_places: u32,
+}
+
+fn de_fixed<'de, D>(de: D) -> Result<Option<SerializedAmount>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // empty in csv, null or missing in json
+    let Some(str) = Option::<String>::deserialize(de)? else {
+        return Ok(None);
+    };
+    if str.is_empty() {
+        return Ok(None);
+    }
+
+    // no asset has more, so anything longer is rejected here rather than scaled
+    let decimal_places = str
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len() as u32)
+        .min(MAX_DECIMAL_PLACES);
+    let raw = parse_decimal(str.as_str(), decimal_places)
+        .map_err(|err| de::Error::custom(err.to_string().as_str()))?;
+
+    if raw > 0 {
+        Ok(Some(SerializedAmount {
+            raw,
+            decimal_places,
+        }))
+    } else {
+        Err(de::Error::custom("Amount must be positive"))
+    }
+}
+
+fn de_asset<'de, D>(de: D) -> Result<Asset, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let str = String::deserialize(de)?;
+    Asset::from_str(str.as_str()).map_err(|err| de::Error::custom(err.to_string().as_str()))
+}
+
+impl fmt::Display for SerializedTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SerializedTransaction {{ type_name: {:?}, client_id: {}, id: {}, amount: {:?}, asset: {} }}",
+            self.type_name, self.client_id, self.transaction_id, self.amount, self.asset
+        )
+    }
+}
+
+impl SerializedTransaction {
+    // amounts are scaled to the decimal places of the row's asset
+    fn to_transaction(&self, scales: &AssetScales) -> Result<UnprocessedTransaction> {
+        let amount = |missing: &'static str| {
+            let amount = self.amount.ok_or_else(|| anyhow!(missing))?;
+            scales.scale(self.asset, amount)
+        };
+        let transaction_type = match self.type_name {
+            SerializedTransactionType::Deposit => {
+                TransactionType::Deposit(amount("Deposits must specify amounts")?)
+            }
+            SerializedTransactionType::Withdrawal => {
+                TransactionType::Withdrawal(amount("Withdrawals must specify amounts")?)
+            }
+            SerializedTransactionType::Dispute => TransactionType::Claim(ClaimType::Dispute),
+            SerializedTransactionType::Resolve => TransactionType::Claim(ClaimType::Resolve),
+            SerializedTransactionType::Chargeback => TransactionType::Claim(ClaimType::Chargeback),
+        };
+
+        let mut transaction = UnprocessedTransaction::new(
+            transaction_type,
+            self.client_id,
+            self.transaction_id,
+            self.timestamp.unwrap_or(self.row),
+            self.asset,
+        );
+        transaction.metadata.row = self.row;
+
+        Ok(transaction)
+    }
+
+    // source and row identify where the transaction was read from, for reporting and as the default chronology
+    pub fn into_transaction(
+        mut self,
+        source: u32,
+        row: u64,
+        scales: &AssetScales,
+    ) -> Result<UnprocessedTransaction, ParseError> {
+        self.row = row;
+        let mut transaction = self
+            .to_transaction(scales)
+            .map_err(|err| ParseError::new(source, row, err.to_string()))?;
+        transaction.metadata.source = source;
+        Ok(transaction)
+    }
+}
@@ -1,11 +1,25 @@
-use serde::Deserialize;
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum SerializedTransactionType {
-    Deposit,
-    Withdrawal,
-    Dispute,
-    Resolve,
-    Chargeback,
-}
+use crate::transactions::TransactionType;
+use crate::transactions::transaction::ClaimType;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerializedTransactionType {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+impl From<&TransactionType> for SerializedTransactionType {
+    fn from(transaction_type: &TransactionType) -> Self {
+        match transaction_type {
+            TransactionType::Deposit(_) => SerializedTransactionType::Deposit,
+            TransactionType::Withdrawal(_) => SerializedTransactionType::Withdrawal,
+            TransactionType::Claim(ClaimType::Dispute) => SerializedTransactionType::Dispute,
+            TransactionType::Claim(ClaimType::Resolve) => SerializedTransactionType::Resolve,
+            TransactionType::Claim(ClaimType::Chargeback) => SerializedTransactionType::Chargeback,
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use memmap2::Mmap;
+
+use crate::io::ParseError;
+use crate::io::merged_sources::STDIN_FILEPATH;
+use crate::transactions::transaction::ClaimType;
+use crate::transactions::{Amount, Asset, TransactionType, UnprocessedTransaction};
+use crate::util::varint::{read_signed_varint, read_varint, write_signed_varint, write_varint};
+
+// a binary encoding of transactions which have already been parsed, so replaying them skips parsing text
+//
+// header: magic "KTXL", u16 version, u16 reserved
+// then blocks of: u32 payload length, u32 record count, u32 crc32 of the payload, payload
+// each record in a payload: u16 record length, then u8 type, client, tx, chronology, source, row,
+//   the i128 raw Amount (deposits and withdrawals only), then the asset code in the remaining bytes
+// raw amounts are in the asset's smallest unit, so a log means the same thing with the --asset-scale it was written with
+// the integers in a record are LEB128 varints, the amount zigzag encoded first, so typical records are under 20 bytes
+// the log ends with an empty block, so a file truncated between blocks is detected too
+// everything else is little endian
+const MAGIC: &[u8; 4] = b"KTXL";
+// version 2 added the source to records
+const VERSION: u16 = 2;
+const HEADER_LENGTH: usize = 8;
+const BLOCK_HEADER_LENGTH: usize = 12;
+// blocks are written once they reach this size
+const TARGET_BLOCK_LENGTH: usize = 64 * 1024;
+
+// buffers records into blocks, call finish once every transaction has been written
+pub struct TransactionLogWriter<W: Write> {
+    writer: W,
+    block: Vec<u8>,
+    block_record_count: u32,
+}
+
+impl TransactionLogWriter<BufWriter<File>> {
+    pub fn create(filepath: &str) -> Result<Self> {
+        let file =
+            File::create(filepath).with_context(|| format!("Failed to create {filepath}"))?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write> TransactionLogWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&0u16.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            block: Vec::with_capacity(TARGET_BLOCK_LENGTH),
+            block_record_count: 0,
+        })
+    }
+
+    // carries on writing blocks after the end of an existing log, which must not have been finished
+    pub fn resume(writer: W) -> Self {
+        Self {
+            writer,
+            block: Vec::with_capacity(TARGET_BLOCK_LENGTH),
+            block_record_count: 0,
+        }
+    }
+
+    pub fn write(&mut self, transaction: &UnprocessedTransaction) -> Result<()> {
+        let (type_code, amount) = match transaction.transaction_type {
+            TransactionType::Deposit(amount) => (0u8, Some(amount)),
+            TransactionType::Withdrawal(amount) => (1, Some(amount)),
+            TransactionType::Claim(ClaimType::Dispute) => (2, None),
+            TransactionType::Claim(ClaimType::Resolve) => (3, None),
+            TransactionType::Claim(ClaimType::Chargeback) => (4, None),
+        };
+        let metadata = &transaction.metadata;
+
+        // the length is filled in once the record is written
+        let length_at = self.block.len();
+        self.block.extend_from_slice(&[0, 0]);
+        self.block.push(type_code);
+        for value in [
+            metadata.client_id,
+            metadata.transaction_id,
+            metadata.chronology,
+            metadata.source as u64,
+            metadata.row,
+        ] {
+            write_varint(&mut self.block, value as u128);
+        }
+        if let Some(amount) = amount {
+            write_signed_varint(&mut self.block, amount.raw());
+        }
+        self.block
+            .extend_from_slice(metadata.asset.as_str().as_bytes());
+
+        let length = (self.block.len() - length_at - 2) as u16;
+        self.block[length_at..length_at + 2].copy_from_slice(&length.to_le_bytes());
+        self.block_record_count += 1;
+
+        if self.block.len() >= TARGET_BLOCK_LENGTH {
+            self.write_block()?;
+        }
+
+        Ok(())
+    }
+
+    // writes out every record so far as a (possibly short) block, then flushes the writer
+    pub fn flush(&mut self) -> Result<&mut W> {
+        if self.block_record_count > 0 {
+            self.write_block()?;
+        }
+        self.writer.flush()?;
+        Ok(&mut self.writer)
+    }
+
+    // writes the last block and the end of the log
+    pub fn finish(mut self) -> Result<W> {
+        if self.block_record_count > 0 {
+            self.write_block()?;
+        }
+        self.write_block()?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_block(&mut self) -> Result<()> {
+        self.writer
+            .write_all(&(self.block.len() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&self.block_record_count.to_le_bytes())?;
+        self.writer
+            .write_all(&crc32fast::hash(&self.block).to_le_bytes())?;
+        self.writer.write_all(&self.block)?;
+
+        self.block.clear();
+        self.block_record_count = 0;
+        Ok(())
+    }
+}
+
+// the whole log, mapped from a file or read from stdin
+enum LogBytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl AsRef<[u8]> for LogBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            LogBytes::Mapped(mmap) => mmap,
+            LogBytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+// memory maps the log and decodes transactions straight out of the mapping, a block at a time
+// a damaged block is reported as a parse error and ends the log, nothing after it can be trusted
+// a filepath of - reads the whole of stdin instead
+pub fn read_transactions_from_log(
+    filepath: &str,
+    source: u32,
+) -> Result<impl Iterator<Item = Result<UnprocessedTransaction, ParseError>> + Send + 'static> {
+    let bytes = if filepath == STDIN_FILEPATH {
+        let mut bytes = vec![];
+        io::stdin().read_to_end(&mut bytes)?;
+        LogBytes::Owned(bytes)
+    } else {
+        let file = File::open(filepath).with_context(|| format!("Failed to open {filepath}"))?;
+        // the log mustn't be modified while it's being read
+        LogBytes::Mapped(unsafe { Mmap::map(&file) }?)
+    };
+
+    check_header(bytes.as_ref(), filepath)?;
+    Ok(LogReader::new(bytes, source))
+}
+
+// what could be read back from a log which may have been cut off part way through
+pub struct RecoveredLog {
+    pub transactions: Vec<UnprocessedTransaction>,
+    // the length of the log up to the end of the last whole block, anything after it should be discarded
+    pub length: u64,
+    // why reading stopped before the end of the file, if it did
+    pub damage: Option<String>,
+}
+
+// reads an unfinished log, e.g. one being written when the process died
+// a truncated or torn block at the end is expected rather than an error, and is dropped
+// a damaged block with anything after it can't have been torn by a crash, so that's an error, as it was synced
+// the end of the log is optional, and counts as the end of the last whole block
+pub fn recover_transaction_log(filepath: &str) -> Result<RecoveredLog> {
+    let file = File::open(filepath).with_context(|| format!("Failed to open {filepath}"))?;
+    // the log mustn't be modified while it's being read
+    let bytes = LogBytes::Mapped(unsafe { Mmap::map(&file) }?);
+    check_header(bytes.as_ref(), filepath)?;
+
+    let mut reader = LogReader::new(bytes, 0);
+    let mut transactions = vec![];
+    loop {
+        let block_start = reader.offset;
+        let damage = if block_start == reader.bytes.as_ref().len() {
+            None
+        } else {
+            match reader.start_block() {
+                Ok(true) => {
+                    // a whole block with a matching checksum can only be unreadable if it was written wrongly
+                    while reader.block_records_left > 0 {
+                        let transaction = reader
+                            .read_record()
+                            .map_err(|reason| anyhow!("{filepath} is corrupt: {reason}"))?;
+                        transactions.push(transaction);
+                        reader.block_records_left -= 1;
+                    }
+                    continue;
+                }
+                Ok(false) => None,
+                Err(reason) => Some(reason),
+            }
+        };
+
+        // a torn block's header may be cut off too, in which case it can only reach the end of the file
+        let bytes = reader.bytes.as_ref();
+        let block_end = bytes
+            .get(block_start..block_start + BLOCK_HEADER_LENGTH)
+            .map(|header| {
+                let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+                block_start + BLOCK_HEADER_LENGTH + length
+            });
+        if block_end.is_some_and(|block_end| block_end < bytes.len()) {
+            return Err(anyhow!(
+                "{filepath} is corrupt before its end: {}",
+                damage.as_deref().unwrap_or("it has blocks after its end")
+            ));
+        }
+
+        return Ok(RecoveredLog {
+            transactions,
+            length: block_start as u64,
+            damage,
+        });
+    }
+}
+
+fn check_header(bytes: &[u8], filepath: &str) -> Result<()> {
+    let header = bytes.get(..HEADER_LENGTH);
+    if header.is_none_or(|header| &header[..4] != MAGIC) {
+        return Err(anyhow!("{filepath} is not a transaction log"));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(anyhow!(
+            "{filepath} is a version {version} transaction log, only version {VERSION} can be read"
+        ));
+    }
+    Ok(())
+}
+
+struct LogReader {
+    bytes: LogBytes,
+    source: u32,
+    // the next unread byte
+    offset: usize,
+    // the end of the current block's payload
+    block_end: usize,
+    block_records_left: u32,
+    // how many records have been read, to place errors
+    rows_read: u64,
+    finished: bool,
+}
+
+impl Iterator for LogReader {
+    type Item = Result<UnprocessedTransaction, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if self.block_records_left == 0 {
+            match self.start_block() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(reason) => return Some(Err(self.fail(reason))),
+            }
+        }
+
+        // the log is read as a single input, so it takes that input's source rather than the recorded one
+        let result = self.read_record().map(|mut transaction| {
+            transaction.metadata.source = self.source;
+            transaction
+        });
+        self.rows_read += 1;
+        self.block_records_left -= 1;
+        Some(result.map_err(|reason| self.fail(reason)))
+    }
+}
+
+impl LogReader {
+    fn new(bytes: LogBytes, source: u32) -> Self {
+        Self {
+            bytes,
+            source,
+            offset: HEADER_LENGTH,
+            block_end: HEADER_LENGTH,
+            block_records_left: 0,
+            rows_read: 0,
+            finished: false,
+        }
+    }
+
+    // checks the next block is whole and uncorrupted, false at the end of the log
+    fn start_block(&mut self) -> Result<bool, String> {
+        let bytes = self.bytes.as_ref();
+        let header = bytes
+            .get(self.offset..self.offset + BLOCK_HEADER_LENGTH)
+            .ok_or("Transaction log is truncated, it has no end")?;
+        let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let record_count = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let checksum = u32::from_le_bytes(header[8..12].try_into().unwrap());
+
+        let start = self.offset + BLOCK_HEADER_LENGTH;
+        let payload = bytes
+            .get(start..start + length)
+            .ok_or("Transaction log is truncated part way through a block")?;
+        if crc32fast::hash(payload) != checksum {
+            return Err("Transaction log block is corrupt, its checksum doesn't match".to_string());
+        }
+
+        if record_count == 0 {
+            return Ok(false);
+        }
+
+        self.offset = start;
+        self.block_end = start + length;
+        self.block_records_left = record_count;
+        Ok(true)
+    }
+
+    fn read_record(&mut self) -> Result<UnprocessedTransaction, String> {
+        let bytes = self.bytes.as_ref();
+        let length = bytes
+            .get(self.offset..self.offset + 2)
+            .filter(|_| self.offset + 2 <= self.block_end)
+            .map(|length| u16::from_le_bytes([length[0], length[1]]) as usize)
+            .ok_or("Transaction log block has fewer records than it says")?;
+        let start = self.offset + 2;
+        if length == 0 || start + length > self.block_end {
+            return Err("Transaction log record has an invalid length".to_string());
+        }
+        let record = &bytes[start..start + length];
+        self.offset = start + length;
+
+        let mut at = 1;
+        let mut integer = || {
+            read_varint(record, &mut at).ok_or("Transaction log record is shorter than its fields")
+        };
+        let mut fields = [0u64; 5];
+        for field in &mut fields {
+            *field = u64::try_from(integer()?).map_err(|err| err.to_string())?;
+        }
+        let [client_id, transaction_id, chronology, source, row] = fields;
+        let mut amount = || {
+            let amount = read_signed_varint(record, &mut at)
+                .map(Amount::from_raw)
+                .ok_or("Transaction log record is shorter than its fields")?;
+            if amount.raw() > 0 {
+                Ok(amount)
+            } else {
+                Err("Amount must be positive")
+            }
+        };
+        let transaction_type = match record[0] {
+            0 => TransactionType::Deposit(amount()?),
+            1 => TransactionType::Withdrawal(amount()?),
+            2 => TransactionType::Claim(ClaimType::Dispute),
+            3 => TransactionType::Claim(ClaimType::Resolve),
+            4 => TransactionType::Claim(ClaimType::Chargeback),
+            type_code => return Err(format!("Unknown transaction type {type_code}")),
+        };
+        let asset = std::str::from_utf8(&record[at..])
+            .map_err(|err| err.to_string())
+            .and_then(|asset| Asset::from_str(asset).map_err(|err| err.to_string()))?;
+
+        let mut transaction = UnprocessedTransaction::new(
+            transaction_type,
+            client_id,
+            transaction_id,
+            chronology,
+            asset,
+        );
+        transaction.metadata.row = row;
+        transaction.metadata.source = u32::try_from(source).map_err(|err| err.to_string())?;
+        Ok(transaction)
+    }
+
+    fn fail(&mut self, reason: String) -> ParseError {
+        self.finished = true;
+        ParseError::new(self.source, self.rows_read, reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_log(transactions: &[UnprocessedTransaction]) -> Vec<u8> {
+        let mut writer = TransactionLogWriter::new(vec![]).unwrap();
+        for transaction in transactions {
+            writer.write(transaction).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn read_log(bytes: Vec<u8>) -> Vec<Result<UnprocessedTransaction, ParseError>> {
+        LogReader::new(LogBytes::Owned(bytes), 0).collect()
+    }
+
+    // enough to span several blocks
+    fn transactions() -> Vec<UnprocessedTransaction> {
+        let btc = Asset::from_str("BTC").unwrap();
+        (0..10_000u64)
+            .map(|i| {
+                let transaction = match i % 4 {
+                    0 => UnprocessedTransaction::deposit(i % 7, i, Amount::from_raw(i as i128 + 1)),
+                    1 => UnprocessedTransaction::withdrawal(i % 7, i, Amount::from_raw(i128::MAX))
+                        .with_asset(btc),
+                    2 => UnprocessedTransaction::dispute(i % 7, i - 2),
+                    _ => UnprocessedTransaction::chargeback(i % 7, i - 3),
+                };
+                let mut transaction = transaction.with_chronology(i * 10);
+                transaction.metadata.row = i;
+                transaction
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips() {
+        let transactions = transactions();
+        let read = read_log(write_log(&transactions));
+
+        assert_eq!(read.len(), transactions.len());
+        for (read, written) in read.into_iter().zip(&transactions) {
+            let read = read.unwrap();
+            assert_eq!(format!("{read:?}"), format!("{written:?}"));
+        }
+    }
+
+    #[test]
+    fn detects_truncation_and_corruption() {
+        let bytes = write_log(&transactions());
+
+        // cutting off the end marker, or part way through a block
+        for length in [bytes.len() - BLOCK_HEADER_LENGTH, bytes.len() / 2] {
+            let read = read_log(bytes[..length].to_vec());
+            let err = read.last().unwrap().as_ref().unwrap_err();
+            assert!(err.reason.contains("truncated"), "{}", err.reason);
+        }
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LENGTH + BLOCK_HEADER_LENGTH + 5] ^= 1;
+        let read = read_log(corrupted);
+        assert_eq!(read.len(), 1);
+        assert!(read[0].as_ref().unwrap_err().reason.contains("checksum"));
+    }
+}
@@ -1,104 +1,254 @@
-use crate::io::SerializedTransactionType;
-use crate::transactions::transaction::ClaimType;
-use crate::transactions::{TransactionType, UnprocessedTransaction};
-use crate::util::Fixed;
-use anyhow::{Result, anyhow};
-use num::Signed;
-use serde::{Deserialize, Deserializer, de};
-use std::fmt;
-use std::fs::File;
-use std::io::BufReader;
-use std::str::FromStr;
-
-#[derive(Debug, Deserialize)]
-struct CsvTransaction {
-    #[serde(rename(deserialize = "type"))]
-    pub type_name: SerializedTransactionType,
-    #[serde(rename(deserialize = "client"))]
-    pub client_id: u64,
-    #[serde(rename(deserialize = "tx"))]
-    pub transaction_id: u64,
-    #[serde(rename(deserialize = "amount"))]
-    #[serde(deserialize_with = "de_fixed")]
-    #[serde(default)]
-    pub amount: Option<Fixed>,
-    #[serde(skip)]
-    pub chronology: u64,
+use crate::io::{AssetScales, ParseError, SerializedTransaction};
+use crate::transactions::UnprocessedTransaction;
+use csv::StringRecord;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type ParsedRow = Result<UnprocessedTransaction, ParseError>;
+
+// records are handed to parser threads in batches to keep channel overhead down
+const BATCH_SIZE: usize = 1024;
+// how many batches can wait to be parsed, and to be handed on once parsed, bounding memory
+const CHANNEL_CAPACITY: usize = 16;
+
+// streams transactions from a reader, one row at a time
+// csv row -> SerializedTransaction -> UnprocessedTransaction
+// a bad row is reported with its row number and does not stop later rows from being read
+// chronology comes from the optional timestamp (or seq) column, falling back to the row number
+// amounts can have as many decimal places as their asset, see AssetScales
+pub fn read_transactions_from_csv<R: Read>(
+    reader: R,
+    source: u32,
+    scales: AssetScales,
+) -> impl Iterator<Item = Result<UnprocessedTransaction, ParseError>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) // allow for whitespace between fields and delimiters
+        .from_reader(reader)
+        .into_deserialize()
+        .enumerate()
+        .map(move |(i, result)| {
+            let row = i as u64;
+            result
+                .map_err(|err| {
+                    ParseError::new(
+                        source,
+                        row,
+                        format!("Failed to deserialize transaction: {err}"),
+                    )
+                })
+                .and_then(|t: SerializedTransaction| t.into_transaction(source, row, &scales))
+        })
 }
 
-fn de_fixed<'de, D>(de: D) -> Result<Option<Fixed>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let str = String::deserialize(de)?;
-    if str.is_empty() {
-        return Ok(None);
-    }
+// the same rows as read_transactions_from_csv, but deserialized on parser threads
+// one thread only splits the input into records, which is much cheaper than deserializing them
+// batches are numbered as they're split, so they come out in the order they were read whichever parser finishes first
+pub fn read_transactions_from_csv_on_threads<R: Read + Send + 'static>(
+    reader: R,
+    source: u32,
+    scales: AssetScales,
+    parsers: usize,
+) -> impl Iterator<Item = ParsedRow> {
+    let (batch_sender, batch_receiver) = sync_channel::<Batch>(CHANNEL_CAPACITY);
+    let (parsed_sender, parsed_receiver) = sync_channel(CHANNEL_CAPACITY);
 
-    let fixed =
-        Fixed::from_str(str.as_str()).map_err(|err| de::Error::custom(err.to_string().as_str()))?;
+    thread::spawn(move || split_records(reader, batch_sender));
 
-    if fixed.is_positive() {
-        Ok(Some(fixed))
-    } else {
-        Err(de::Error::custom("Amount must be positive"))
+    // parsers take whichever batch is next, so a slow batch doesn't hold up the others
+    let batch_receiver = Arc::new(Mutex::new(batch_receiver));
+    for _ in 0..parsers.max(1) {
+        let batch_receiver = batch_receiver.clone();
+        let parsed_sender = parsed_sender.clone();
+        let scales = scales.clone();
+        thread::spawn(move || {
+            loop {
+                let Ok(batch) = batch_receiver.lock().expect("Csv splitter panicked").recv() else {
+                    return;
+                };
+                let sequence = batch.sequence;
+                // the reader has been dropped, nothing left to parse for
+                if parsed_sender
+                    .send((sequence, batch.parse(source, &scales)))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
     }
+
+    InOrder {
+        receiver: parsed_receiver,
+        parsed: BTreeMap::new(),
+        next_sequence: 0,
+        rows: vec![].into_iter(),
+    }
+}
+
+struct Batch {
+    sequence: u64,
+    first_row: u64,
+    headers: Arc<StringRecord>,
+    records: Vec<csv::Result<StringRecord>>,
 }
 
-impl fmt::Display for CsvTransaction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "CsvTransaction {{ type_name: {:?}, client_id: {}, id: {}, amount: {:?} }}",
-            self.type_name, self.client_id, self.transaction_id, self.amount
-        )
+impl Batch {
+    fn parse(self, source: u32, scales: &AssetScales) -> Vec<ParsedRow> {
+        let headers = self.headers;
+        self.records
+            .into_iter()
+            .zip(self.first_row..)
+            .map(|(record, row)| {
+                record
+                    .and_then(|record| record.deserialize::<SerializedTransaction>(Some(&headers)))
+                    .map_err(|err| {
+                        ParseError::new(
+                            source,
+                            row,
+                            format!("Failed to deserialize transaction: {err}"),
+                        )
+                    })
+                    .and_then(|t| t.into_transaction(source, row, scales))
+            })
+            .collect()
     }
 }
 
-impl TryFrom<CsvTransaction> for UnprocessedTransaction {
-    type Error = anyhow::Error;
-
-    fn try_from(csv_transaction: CsvTransaction) -> Result<Self> {
-        let transaction_type = match csv_transaction.type_name {
-            SerializedTransactionType::Deposit | SerializedTransactionType::Withdrawal => {
-                TransactionType::UpdateFunds(
-                    csv_transaction
-                        .amount
-                        .ok_or(anyhow!("Deposits and Withdrawals must specify amounts"))?,
-                )
+// rows are numbered the same as read_transactions_from_csv, a record which can't be read still takes a row
+fn split_records<R: Read>(reader: R, sender: SyncSender<Batch>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = match reader.headers() {
+        Ok(headers) => Arc::new(headers.clone()),
+        Err(err) => {
+            let _ = sender.send(Batch {
+                sequence: 0,
+                first_row: 0,
+                headers: Arc::default(),
+                records: vec![Err(err)],
+            });
+            return;
+        }
+    };
+
+    let mut batch = Batch {
+        sequence: 0,
+        first_row: 0,
+        headers: headers.clone(),
+        records: Vec::with_capacity(BATCH_SIZE),
+    };
+    loop {
+        let mut record = StringRecord::new();
+        let stop = match reader.read_record(&mut record) {
+            Ok(true) => {
+                batch.records.push(Ok(record));
+                false
+            }
+            Ok(false) => true,
+            Err(err) => {
+                // the input itself failed, carrying on would only report the same error again
+                let stop = err.is_io_error();
+                batch.records.push(Err(err));
+                stop
             }
-            SerializedTransactionType::Dispute => TransactionType::Claim(ClaimType::Dispute),
-            SerializedTransactionType::Resolve => TransactionType::Claim(ClaimType::Resolve),
-            SerializedTransactionType::Chargeback => TransactionType::Claim(ClaimType::Chargeback),
         };
 
-        Ok(UnprocessedTransaction::new(
-            transaction_type,
-            csv_transaction.client_id,
-            csv_transaction.transaction_id,
-            csv_transaction.chronology,
-        ))
+        if batch.records.len() >= BATCH_SIZE || (stop && !batch.records.is_empty()) {
+            let next = Batch {
+                sequence: batch.sequence + 1,
+                first_row: batch.first_row + batch.records.len() as u64,
+                headers: headers.clone(),
+                records: Vec::with_capacity(BATCH_SIZE),
+            };
+            // the reader has been dropped, nothing left to split for
+            if sender.send(std::mem::replace(&mut batch, next)).is_err() {
+                return;
+            }
+        }
+        if stop {
+            return;
+        }
+    }
+}
+
+// hands on parsed batches in sequence, holding back any which were parsed ahead of an earlier one
+struct InOrder {
+    receiver: Receiver<(u64, Vec<ParsedRow>)>,
+    parsed: BTreeMap<u64, Vec<ParsedRow>>,
+    next_sequence: u64,
+    rows: std::vec::IntoIter<ParsedRow>,
+}
+
+impl Iterator for InOrder {
+    type Item = ParsedRow;
+
+    fn next(&mut self) -> Option<ParsedRow> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(row);
+            }
+            if let Some(rows) = self.parsed.remove(&self.next_sequence) {
+                self.next_sequence += 1;
+                self.rows = rows.into_iter();
+                continue;
+            }
+
+            match self.receiver.recv() {
+                Ok((sequence, rows)) => {
+                    self.parsed.insert(sequence, rows);
+                }
+                // every parser has stopped, which without a gap means the input has all been read
+                Err(_) => {
+                    assert!(self.parsed.is_empty(), "Csv parser thread panicked");
+                    return None;
+                }
+            }
+        }
     }
 }
 
-// reads transactions from a file
-// csv row -> CsvTransaction -> UnprocessedTransaction
-pub fn read_transactions_from_csv_file(filepath: &str) -> Result<Vec<UnprocessedTransaction>> {
-    let file = File::open(filepath)?;
-    let buf_reader = BufReader::new(file);
-    csv::ReaderBuilder::new()
-        .trim(csv::Trim::All) // allow for whitespace between fields and delimiters
-        .from_reader(buf_reader)
-        .into_deserialize()
-        .enumerate()
-        .map(|(i, result)| {
-            result
-                .map(|mut t: CsvTransaction| {
-                    t.chronology = i as u64;
-                    t
-                })
-                .map_err(|err| anyhow!("Failed to deserialize transaction: {err}"))?
-                .try_into()
-        })
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn parsing_on_threads_matches_parsing_in_order() {
+        let mut input = "type, client, tx, amount, asset\n".to_string();
+        for tx in 0..5 * BATCH_SIZE as u64 {
+            input.push_str(&match tx % 100 {
+                // bad amounts, a missing amount, a short row and too many decimal places
+                7 => format!("deposit,1,{tx},abc,\n"),
+                13 => format!("withdrawal,2,{tx},,\n"),
+                21 => format!("deposit,3,{tx}\n"),
+                34 => format!("deposit,4,{tx},1.00001,\n"),
+                55 => format!("dispute,5,{},,\n", tx - 1),
+                _ => format!("deposit,{},{tx},{}.5,BTC\n", tx % 9, tx),
+            });
+        }
+        let scales: AssetScales = ["BTC=8".parse().unwrap()].into_iter().collect();
+
+        let in_order: Vec<String> =
+            read_transactions_from_csv(Cursor::new(input.clone()), 2, scales.clone())
+                .map(|row| format!("{row:?}"))
+                .collect();
+        for parsers in [1, 3] {
+            let on_threads: Vec<String> = read_transactions_from_csv_on_threads(
+                Cursor::new(input.clone()),
+                2,
+                scales.clone(),
+                parsers,
+            )
+            .map(|row| format!("{row:?}"))
+            .collect();
+            assert_eq!(on_threads, in_order);
+        }
+        assert_eq!(in_order.len(), 5 * BATCH_SIZE);
+        assert!(in_order.iter().any(|row| row.starts_with("Err")));
+    }
 }
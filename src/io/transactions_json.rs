@@ -0,0 +1,32 @@
+use crate::io::{AssetScales, ParseError, SerializedTransaction};
+use crate::transactions::UnprocessedTransaction;
+use std::io::BufRead;
+
+// streams transactions from newline delimited json, one object per line, blank lines are skipped
+// {"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}
+// amounts are strings so they are never rounded through a float
+// rows are numbered by line, starting from 0
+pub fn read_transactions_from_json_lines<R: BufRead>(
+    reader: R,
+    source: u32,
+    scales: AssetScales,
+) -> impl Iterator<Item = Result<UnprocessedTransaction, ParseError>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(move |(i, line)| {
+            let row = i as u64;
+            line.map_err(|err| ParseError::new(source, row, format!("Failed to read line: {err}")))
+                .and_then(|line| {
+                    serde_json::from_str::<SerializedTransaction>(&line).map_err(|err| {
+                        ParseError::new(
+                            source,
+                            row,
+                            format!("Failed to deserialize transaction: {err}"),
+                        )
+                    })
+                })
+                .and_then(|t| t.into_transaction(source, row, &scales))
+        })
+}
@@ -0,0 +1,212 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::io::transaction_log::{TransactionLogWriter, recover_transaction_log};
+use crate::transactions::UnprocessedTransaction;
+
+// every transaction applied, accepted or rejected, appended so a run which dies part way through can pick up where it was
+// the log is a transaction log which is never finished, each sync writes out the records so far as a block
+// a crash can leave a torn block at the end, which is dropped when the log is next opened
+pub struct WriteAheadLog {
+    writer: TransactionLogWriter<BufWriter<File>>,
+    policy: SyncPolicy,
+    unsynced: usize,
+    last_sync: Instant,
+}
+
+// when appended transactions are flushed to disk, there's no timer, syncs only happen as transactions are appended
+// anything not yet synced when the process dies is applied again from the input on restart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncPolicy {
+    // sync once this many transactions have been appended since the last sync
+    Records(usize),
+    // sync on the first append this long after the last sync, however long that takes to arrive
+    FirstAppendAfter(Duration),
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        SyncPolicy::Records(1000)
+    }
+}
+
+impl FromStr for SyncPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid sync policy {s}, expected records=N or ms=T");
+        let (unit, count) = s.split_once('=').ok_or_else(invalid)?;
+        let count = count
+            .parse::<u64>()
+            .map_err(|err| anyhow!("Invalid sync policy {s}: {err}"))?;
+        match unit {
+            "records" if count > 0 => Ok(SyncPolicy::Records(count as usize)),
+            "records" => Err(anyhow!(
+                "Invalid sync policy {s}, records must be at least 1"
+            )),
+            "ms" => Ok(SyncPolicy::FirstAppendAfter(Duration::from_millis(count))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl WriteAheadLog {
+    // opens the log at filepath, creating it if it doesn't exist, along with every transaction already in it
+    pub fn open(filepath: &str, policy: SyncPolicy) -> Result<(Self, Vec<UnprocessedTransaction>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(filepath)
+            .with_context(|| format!("Failed to open {filepath}"))?;
+
+        let (writer, transactions) = if file.metadata()?.len() == 0 {
+            let mut writer = TransactionLogWriter::new(BufWriter::new(file))?;
+            writer.flush()?.get_ref().sync_data()?;
+            (writer, vec![])
+        } else {
+            let recovered = recover_transaction_log(filepath)?;
+            if let Some(damage) = recovered.damage {
+                eprintln!("Discarding the end of {filepath}: {damage}");
+            }
+            // later blocks are appended after the last whole one
+            file.set_len(recovered.length)?;
+            file.seek(SeekFrom::End(0))?;
+            file.sync_data()?;
+            (
+                TransactionLogWriter::resume(BufWriter::new(file)),
+                recovered.transactions,
+            )
+        };
+
+        Ok((
+            Self {
+                writer,
+                policy,
+                unsynced: 0,
+                last_sync: Instant::now(),
+            },
+            transactions,
+        ))
+    }
+
+    pub fn append(&mut self, transaction: &UnprocessedTransaction) -> Result<()> {
+        self.writer.write(transaction)?;
+        self.unsynced += 1;
+
+        let due = match self.policy {
+            SyncPolicy::Records(count) => self.unsynced >= count,
+            SyncPolicy::FirstAppendAfter(interval) => self.last_sync.elapsed() >= interval,
+        };
+        if due {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    // makes everything appended so far durable
+    pub fn sync(&mut self) -> Result<()> {
+        if self.unsynced == 0 {
+            return Ok(());
+        }
+
+        self.writer.flush()?.get_ref().sync_data()?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::Amount;
+    use std::fs;
+
+    fn temp_filepath(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kraken-{}-{name}.wal", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    fn deposit(id: u64) -> UnprocessedTransaction {
+        UnprocessedTransaction::deposit(1, id, Amount::from_raw(id as i128 + 1)).with_chronology(id)
+    }
+
+    #[test]
+    fn recovers_synced_transactions_and_drops_a_torn_tail() {
+        let filepath = temp_filepath("torn");
+
+        let (mut wal, recovered) = WriteAheadLog::open(&filepath, SyncPolicy::Records(2)).unwrap();
+        assert!(recovered.is_empty());
+        for id in 0..5 {
+            wal.append(&deposit(id)).unwrap();
+        }
+        drop(wal);
+        // the last record was never synced, and the process died part way through writing the block before it
+        let length = fs::metadata(&filepath).unwrap().len();
+        let torn = File::options().write(true).open(&filepath).unwrap();
+        torn.set_len(length - 3).unwrap();
+
+        let (mut wal, recovered) = WriteAheadLog::open(&filepath, SyncPolicy::Records(1)).unwrap();
+        let ids: Vec<u64> = recovered
+            .iter()
+            .map(|t| t.metadata.transaction_id)
+            .collect();
+        assert_eq!(ids, [0, 1]);
+
+        // appending carries on from the last whole block
+        wal.append(&deposit(2)).unwrap();
+        drop(wal);
+        let (_, recovered) = WriteAheadLog::open(&filepath, SyncPolicy::default()).unwrap();
+        let ids: Vec<u64> = recovered
+            .iter()
+            .map(|t| t.metadata.transaction_id)
+            .collect();
+        assert_eq!(ids, [0, 1, 2]);
+
+        fs::remove_file(&filepath).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_drop_damage_before_the_end() {
+        let filepath = temp_filepath("corrupt");
+
+        let (mut wal, _) = WriteAheadLog::open(&filepath, SyncPolicy::Records(1)).unwrap();
+        for id in 0..3 {
+            wal.append(&deposit(id)).unwrap();
+        }
+        drop(wal);
+        // a bit flipped in the first block's payload, with two synced blocks after it
+        let mut bytes = fs::read(&filepath).unwrap();
+        bytes[8 + 12] ^= 1;
+        fs::write(&filepath, &bytes).unwrap();
+
+        let err = WriteAheadLog::open(&filepath, SyncPolicy::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("corrupt before its end"), "{err}");
+        assert_eq!(fs::read(&filepath).unwrap(), bytes);
+
+        fs::remove_file(&filepath).unwrap();
+    }
+
+    #[test]
+    fn parses_sync_policies() {
+        assert_eq!(
+            "records=10".parse::<SyncPolicy>().unwrap(),
+            SyncPolicy::Records(10)
+        );
+        assert_eq!(
+            "ms=50".parse::<SyncPolicy>().unwrap(),
+            SyncPolicy::FirstAppendAfter(Duration::from_millis(50))
+        );
+        assert!("records=0".parse::<SyncPolicy>().is_err());
+        assert!("seconds=1".parse::<SyncPolicy>().is_err());
+    }
+}
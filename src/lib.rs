@@ -0,0 +1,32 @@
+//! Applies deposits, withdrawals and claims (disputes, resolves and chargebacks) to client accounts.
+//!
+//! ```
+//! use kraken::{Amount, Engine, TransactionError, UnprocessedTransaction};
+//! use std::str::FromStr;
+//!
+//! let mut engine = Engine::new();
+//! let amount = Amount::from_str("1.5").unwrap();
+//! engine.apply(UnprocessedTransaction::deposit(1, 1, amount).with_chronology(1)).unwrap();
+//! assert_eq!(
+//!     engine.apply(UnprocessedTransaction::withdrawal(1, 2, amount + amount).with_chronology(2)),
+//!     Err(TransactionError::InsufficientFunds)
+//! );
+//! engine.finish();
+//!
+//! let client = engine.client(1).unwrap();
+//! assert_eq!(client.state(Default::default()).available_funds(), amount);
+//! ```
+
+pub mod clients;
+pub mod engines;
+pub mod io;
+pub mod servers;
+pub mod transactions;
+pub mod util;
+
+pub use clients::{Client, ClientConfig, ClientSnapshot, ClientStore};
+#[cfg(feature = "async")]
+pub use engines::AsyncEngine;
+pub use engines::{Engine, ShardedEngine};
+pub use transactions::{Amount, Asset, TransactionError, TransactionType, UnprocessedTransaction};
+pub use util::Fixed;
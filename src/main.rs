@@ -1,41 +1,283 @@
-pub mod clients;
-pub mod io;
-pub mod transactions;
-pub mod util;
+use anyhow::{Context, Result, anyhow};
+use kraken::clients::{Client, ClientConfig, ClientStore, DenseClientStore, HashClientStore};
+use kraken::engines::{Engine, ShardedEngine};
+use kraken::io::merged_sources::{read_transactions_from_file, read_transactions_from_files};
+use kraken::io::opening_balances_csv::read_opening_balances_from_file;
+use kraken::io::rejections_csv::RejectionWriter;
+use kraken::io::serialized_client::*;
+use kraken::io::transaction_log::TransactionLogWriter;
+use kraken::io::write_ahead_log::WriteAheadLog;
+use kraken::io::{AssetScales, ParseError};
+use kraken::transactions::{TransactionError, UnprocessedTransaction};
+use kraken::util::Cli;
+use kraken::util::cli::{Command, ConvertArgs};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 
-use crate::clients::Client;
-use crate::io::serialized_client::*;
-use io::transactions_csv::*;
-use util::Cli;
+fn main() -> Result<()> {
+    let cli = Cli::from_args();
 
-fn main() {
-    let csv_filepath = Cli::from_args().csv_filepath;
+    match &cli.command {
+        Some(Command::Serve(args)) => return kraken::servers::serve(args),
+        Some(Command::Convert(args)) => return convert(args),
+        None => {}
+    }
+
+    match (cli.threads > 1, cli.dense_clients) {
+        (false, false) => run(Engine::new(), &cli),
+        (false, true) => run(Engine::with_store(DenseClientStore::default()), &cli),
+        (true, false) => run_sharded::<HashClientStore>(&cli),
+        (true, true) => run_sharded::<DenseClientStore>(&cli),
+    }
+}
+
+fn client_config(cli: &Cli) -> ClientConfig {
+    ClientConfig {
+        allow_redispute: !cli.no_redispute,
+    }
+}
+
+fn run<S: ClientStore>(engine: Engine<S>, cli: &Cli) -> Result<()> {
+    let mut engine = engine
+        .with_client_config(client_config(cli))
+        .with_reorder_window(cli.reorder_window);
+
+    // before the write-ahead log, which holds what was applied on top of the snapshot
+    if let Some(filepath) = cli.restore.as_deref() {
+        let file = File::open(filepath).with_context(|| format!("Failed to open {filepath}"))?;
+        engine
+            .restore(BufReader::new(file))
+            .with_context(|| format!("Failed to restore {filepath}"))?;
+    }
+    if let Some(filepath) = cli.opening_balances.as_deref() {
+        engine.open_balances(read_opening_balances_from_file(
+            filepath,
+            &asset_scales(cli),
+        )?)?;
+    }
+
+    let mut rejection_writer = cli
+        .rejections
+        .as_deref()
+        .map(|filepath| create_rejection_writer(filepath, cli))
+        .transpose()?;
+
+    let mut write_ahead_log = cli
+        .wal
+        .as_deref()
+        .map(|filepath| open_write_ahead_log(filepath, &mut engine, &mut rejection_writer, cli))
+        .transpose()?;
+
+    let mut parse_error_count = 0;
+
+    // stream transactions from the csv file straight into the engine
+    for transaction in
+        read_transactions_from_files(&cli.filepaths, cli.input_format, &asset_scales(cli), 1)?
+    {
+        let transaction = match transaction {
+            Ok(transaction) => transaction,
+            Err(err) => {
+                parse_error_count += 1;
+                if !cli.on_parse_error.tolerates(parse_error_count) {
+                    return Err(anyhow!(describe_parse_error(&err, cli)));
+                }
+
+                // record the skipped row
+                match rejection_writer.as_mut() {
+                    Some(rejection_writer) => rejection_writer.write_parse_error(&err)?,
+                    None => eprintln!("Skipping {}", describe_parse_error(&err, cli)),
+                }
+                continue;
+            }
+        };
+
+        if engine.is_replayed(&transaction) {
+            continue;
+        }
+
+        // rejected transactions don't affect the output, but can be reported
+        if let Err(err) = engine.submit(transaction) {
+            report_rejection(&mut rejection_writer, &transaction, &err)?;
+        }
+        apply_ready(&mut engine, &mut rejection_writer, &mut write_ahead_log)?;
+    }
+
+    engine.finish();
+    apply_ready(&mut engine, &mut rejection_writer, &mut write_ahead_log)?;
+
+    if let Some(write_ahead_log) = write_ahead_log.as_mut() {
+        write_ahead_log.sync()?;
+    }
+
+    if let Some(filepath) = cli.snapshot.as_deref() {
+        let file =
+            File::create(filepath).with_context(|| format!("Failed to create {filepath}"))?;
+        engine.snapshot(BufWriter::new(file))?;
+    }
+
+    if let Some(rejection_writer) = rejection_writer.as_mut() {
+        rejection_writer.flush()?;
+    }
+
+    write_accounts(&engine.clients(), cli)
+}
 
-    // indexed by client_id (client_id 0 is allowed by this code)
-    let mut clients: Vec<Option<Client>> = vec![];
+// same as run, but rejections are only known once every shard has finished so they are reported at the end
+fn run_sharded<S: ClientStore + Default + Send + 'static>(cli: &Cli) -> Result<()> {
+    if let Some(option) = cli.single_threaded_options().first() {
+        return Err(anyhow!("{option} can't be used with more than one thread"));
+    }
+    let mut engine = ShardedEngine::<S>::new(cli.threads, client_config(cli), cli.reorder_window);
+
+    let mut parse_errors = vec![];
 
-    // read transactions from csv file
-    let unprocessed_transactions = read_transactions_from_csv_file(csv_filepath.as_str()).unwrap();
+    // csv is parsed on as many threads as transactions are applied on, so parsing doesn't hold up the shards
+    for transaction in read_transactions_from_files(
+        &cli.filepaths,
+        cli.input_format,
+        &asset_scales(cli),
+        cli.threads,
+    )? {
+        match transaction {
+            Ok(transaction) => engine.submit(transaction),
+            Err(err) => {
+                if !cli.on_parse_error.tolerates(parse_errors.len() + 1) {
+                    return Err(anyhow!(describe_parse_error(&err, cli)));
+                }
+                parse_errors.push((engine.next_sequence(), err));
+            }
+        }
+    }
 
-    for transaction in unprocessed_transactions {
-        let client_id = transaction.metadata.client_id as usize;
+    let outcome = engine.finish();
 
-        // if client_id is off the end of the current list of clients
-        if client_id >= clients.len() {
-            clients.resize_with(client_id + 1, || None);
+    match cli.rejections.as_deref() {
+        Some(filepath) => {
+            let mut rejection_writer = create_rejection_writer(filepath, cli)?;
+            let mut parse_errors = parse_errors.into_iter().peekable();
+            for (sequence, (transaction, err)) in outcome.rejections {
+                // interleave parse errors so the report matches the single threaded one
+                while let Some((_, parse_error)) =
+                    parse_errors.next_if(|(parse_sequence, _)| *parse_sequence < sequence)
+                {
+                    rejection_writer.write_parse_error(&parse_error)?;
+                }
+                rejection_writer.write(&transaction, &err)?;
+            }
+            for (_, parse_error) in parse_errors {
+                rejection_writer.write_parse_error(&parse_error)?;
+            }
+            rejection_writer.flush()?;
         }
+        None => {
+            for (_, parse_error) in parse_errors {
+                eprintln!("Skipping {}", describe_parse_error(&parse_error, cli));
+            }
+        }
+    }
+
+    let clients: Vec<&Client> = outcome.clients.iter().collect();
+    write_accounts(&clients, cli)
+}
 
-        if let Some(client) = clients.get_mut(client_id) {
-            let client = client.get_or_insert(Client::new(client_id as u64));
-            client.handle_transaction(transaction).unwrap();
-        } else {
-            // unreachable error
+// parses once and writes the transactions as a binary log, which later runs can read without parsing
+fn convert(args: &ConvertArgs) -> Result<()> {
+    let mut log_writer = TransactionLogWriter::create(&args.output)?;
+    let mut parse_error_count = 0;
+    let scales: AssetScales = args.asset_scales.iter().copied().collect();
+
+    for transaction in read_transactions_from_file(&args.input, args.input_format, 0, &scales, 1)? {
+        match transaction {
+            Ok(transaction) => log_writer.write(&transaction)?,
+            Err(err) => {
+                parse_error_count += 1;
+                if !args.on_parse_error.tolerates(parse_error_count) {
+                    return Err(err.into());
+                }
+                eprintln!("Skipping {err}");
+            }
         }
     }
 
-    for client in clients.iter_mut().flatten() {
-        client.calculate_funds().unwrap();
+    log_writer.finish()?;
+    Ok(())
+}
+
+fn write_accounts(clients: &[&Client], cli: &Cli) -> Result<()> {
+    let scales = asset_scales(cli);
+    match cli.output.as_deref() {
+        Some(filepath) => {
+            write_clients_to_file(filepath, clients, cli.layout, cli.output_format, &scales)
+        }
+        None => write_clients_to_stdout(
+            clients,
+            cli.layout,
+            cli.output_format.unwrap_or_default(),
+            &scales,
+        ),
+    }
+}
+
+fn asset_scales(cli: &Cli) -> AssetScales {
+    cli.asset_scales.iter().copied().collect()
+}
+
+// rows are numbered per input, so the report names the input when there's more than one
+fn create_rejection_writer(filepath: &str, cli: &Cli) -> Result<RejectionWriter> {
+    let rejection_writer = RejectionWriter::create(filepath)?;
+    Ok(match cli.filepaths.len() {
+        1 => rejection_writer,
+        _ => rejection_writer.with_source_names(cli.filepaths.clone()),
+    })
+}
+
+fn describe_parse_error(err: &ParseError, cli: &Cli) -> String {
+    match cli.filepaths.len() {
+        1 => err.to_string(),
+        _ => format!("{}: {err}", cli.filepaths[err.source as usize]),
+    }
+}
+
+// rebuilds balances from an earlier run's log, so the input it got through is skipped
+fn open_write_ahead_log<S: ClientStore>(
+    filepath: &str,
+    engine: &mut Engine<S>,
+    rejection_writer: &mut Option<RejectionWriter>,
+    cli: &Cli,
+) -> Result<WriteAheadLog> {
+    let (write_ahead_log, transactions) = WriteAheadLog::open(filepath, cli.wal_sync)?;
+    for (transaction, err) in engine.replay(transactions) {
+        report_rejection(rejection_writer, &transaction, &err)?;
     }
+    Ok(write_ahead_log)
+}
 
-    write_clients_to_stdout(&clients).unwrap();
+// applies every transaction which has left the engine's reorder window
+fn apply_ready<S: ClientStore>(
+    engine: &mut Engine<S>,
+    rejection_writer: &mut Option<RejectionWriter>,
+    write_ahead_log: &mut Option<WriteAheadLog>,
+) -> Result<()> {
+    while let Some((transaction, result)) = engine.poll() {
+        // rejected transactions are logged too, replaying has to take the same ids
+        if let Some(write_ahead_log) = write_ahead_log.as_mut() {
+            write_ahead_log.append(&transaction)?;
+        }
+        if let Err(err) = result {
+            report_rejection(rejection_writer, &transaction, &err)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn report_rejection(
+    rejection_writer: &mut Option<RejectionWriter>,
+    transaction: &UnprocessedTransaction,
+    err: &TransactionError,
+) -> Result<()> {
+    match rejection_writer.as_mut() {
+        Some(rejection_writer) => rejection_writer.write(transaction, err),
+        None => Ok(()),
+    }
 }
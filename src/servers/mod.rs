@@ -0,0 +1,5 @@
+pub mod query;
+pub use query::Query;
+
+pub mod server;
+pub use server::serve;
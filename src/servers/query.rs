@@ -0,0 +1,31 @@
+use anyhow::{Result, anyhow};
+use std::str::FromStr;
+
+// a request made to the query port, one line per connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Query {
+    // balances of one client
+    Client(u64),
+    // balances of every client
+    All,
+    // stop accepting transactions and write the final balances
+    Shutdown,
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["client", client_id] => client_id
+                .parse()
+                .map(Query::Client)
+                .map_err(|err| anyhow!("Invalid client id {client_id}: {err}")),
+            ["all"] => Ok(Query::All),
+            ["shutdown"] => Ok(Query::Shutdown),
+            _ => Err(anyhow!(
+                "Unknown query {s:?}, expected client <id>, all or shutdown"
+            )),
+        }
+    }
+}
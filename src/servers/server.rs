@@ -0,0 +1,268 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{Context, Result};
+
+use crate::clients::{Client, ClientConfig};
+use crate::engines::Engine;
+use crate::io::rejections_csv::RejectionWriter;
+use crate::io::serialized_client::{AccountsLayout, write_clients, write_clients_to_stdout};
+use crate::io::transactions_csv::read_transactions_from_csv;
+use crate::io::transactions_json::read_transactions_from_json_lines;
+use crate::io::{AssetScales, OutputFormat, ParseError};
+use crate::servers::Query;
+use crate::transactions::UnprocessedTransaction;
+use crate::util::cli::ServeArgs;
+
+// everything the engine is asked to do, handled one at a time in the order they're sent
+enum Request {
+    // a new ingest connection, named by its peer address
+    Connected(String),
+    Transaction(UnprocessedTransaction),
+    Invalid(ParseError),
+    Query(Query, Sender<Vec<u8>>),
+    Shutdown,
+    // every ingest connection has closed, so everything they read is already queued
+    IngestStopped,
+}
+
+// how many requests can be waiting for the engine, past this connections stop being read until it catches up
+const QUEUE_CAPACITY: usize = 1024;
+
+// applies transactions streamed to the listen address as they arrive, until a shutdown query or ctrl-c
+// each connection is its own source, sending either csv (starting with a header) or one json object per line
+// the final balances are written to stdout on shutdown, the same as reading from files
+// shutting down stops accepting connections, and applies every row already read before writing them
+pub fn serve(args: &ServeArgs) -> Result<()> {
+    let ingest_listener = TcpListener::bind(&args.listen)
+        .with_context(|| format!("Failed to listen on {}", args.listen))?;
+    let query_listener = TcpListener::bind(&args.query)
+        .with_context(|| format!("Failed to listen on {}", args.query))?;
+
+    let mut engine = Engine::new().with_client_config(ClientConfig {
+        allow_redispute: !args.no_redispute,
+    });
+    let mut rejection_writer = args
+        .rejections
+        .as_deref()
+        .map(RejectionWriter::create)
+        .transpose()?;
+    let scales: AssetScales = args.asset_scales.iter().copied().collect();
+
+    let (requests, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+
+    let interrupt = requests.clone();
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(Request::Shutdown);
+    })?;
+
+    eprintln!(
+        "Accepting transactions on {}, queries on {}",
+        ingest_listener.local_addr()?,
+        query_listener.local_addr()?
+    );
+
+    let stopping = Arc::new(AtomicBool::new(false));
+    let ingest_address = ingest_listener.local_addr()?;
+    let (ingest_requests, ingest_stopping) = (requests.clone(), stopping.clone());
+    let ingest_scales = scales.clone();
+    thread::spawn(move || {
+        accept_ingest(
+            ingest_listener,
+            ingest_requests,
+            &ingest_stopping,
+            &ingest_scales,
+        )
+    });
+    let (layout, output_format) = (args.layout, args.output_format);
+    thread::spawn(move || accept_queries(query_listener, requests));
+
+    // transactions from every connection are interleaved, so their arrival order is their chronology
+    let mut chronology = 0;
+    for request in receiver {
+        match request {
+            Request::Connected(source_name) => {
+                if let Some(rejection_writer) = rejection_writer.as_mut() {
+                    rejection_writer.add_source_name(source_name);
+                }
+            }
+            Request::Transaction(mut transaction) => {
+                chronology += 1;
+                transaction.metadata.chronology = chronology;
+                if let Err(err) = engine.apply(transaction)
+                    && let Some(rejection_writer) = rejection_writer.as_mut()
+                {
+                    rejection_writer.write(&transaction, &err)?;
+                }
+            }
+            // a bad row doesn't stop the rest of the connection from being applied
+            Request::Invalid(err) => match rejection_writer.as_mut() {
+                Some(rejection_writer) => rejection_writer.write_parse_error(&err)?,
+                None => eprintln!("Skipping {err}"),
+            },
+            Request::Query(query, reply) => {
+                let _ = reply.send(answer(&engine, query, layout, output_format, &scales)?);
+            }
+            // keep applying what's queued until the ingest connections have all finished
+            Request::Shutdown => {
+                if !stopping.swap(true, Ordering::SeqCst) {
+                    // wakes the acceptor, which is waiting for a connection, so it sees it should stop
+                    let _ = TcpStream::connect(ingest_address);
+                }
+            }
+            Request::IngestStopped => break,
+        }
+    }
+
+    if let Some(rejection_writer) = rejection_writer.as_mut() {
+        rejection_writer.flush()?;
+    }
+
+    write_clients_to_stdout(&engine.clients(), layout, output_format, &scales)
+}
+
+fn answer(
+    engine: &Engine,
+    query: Query,
+    layout: AccountsLayout,
+    output_format: OutputFormat,
+    scales: &AssetScales,
+) -> Result<Vec<u8>> {
+    let clients: Vec<&Client> = match query {
+        Query::Client(client_id) => match engine.client(client_id) {
+            Some(client) => vec![client],
+            None => return Ok(format!("error: unknown client {client_id}\n").into_bytes()),
+        },
+        Query::All => engine.clients(),
+        Query::Shutdown => unreachable!("Shutdown queries are handled by the connection"),
+    };
+
+    let mut response = vec![];
+    write_clients(&mut response, &clients, layout, output_format, scales)?;
+    Ok(response)
+}
+
+fn accept_ingest(
+    listener: TcpListener,
+    requests: SyncSender<Request>,
+    stopping: &AtomicBool,
+    scales: &AssetScales,
+) {
+    // each open connection's stream, kept so it can be closed on shutdown
+    let mut connections: Vec<(TcpStream, JoinHandle<()>)> = vec![];
+    let mut source = 0;
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        connections.retain(|(_, handle)| !handle.is_finished());
+
+        // the engine has to know the source's name before any of its rows arrive
+        let source_name = stream
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        if requests.send(Request::Connected(source_name)).is_err() {
+            return;
+        }
+
+        let Ok(closer) = stream.try_clone() else {
+            continue;
+        };
+        let (requests, scales) = (requests.clone(), scales.clone());
+        let handle = thread::spawn(move || {
+            if let Err(err) = handle_ingest(stream, source, &requests, &scales) {
+                eprintln!("Ingest connection {source} failed: {err}");
+            }
+        });
+        connections.push((closer, handle));
+        source += 1;
+    }
+
+    // connections still open stop at whatever they've read so far, which is still queued before they finish
+    for (stream, handle) in connections {
+        let _ = stream.shutdown(Shutdown::Read);
+        let _ = handle.join();
+    }
+    let _ = requests.send(Request::IngestStopped);
+}
+
+fn handle_ingest(
+    stream: TcpStream,
+    source: u32,
+    requests: &SyncSender<Request>,
+    scales: &AssetScales,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    // json objects start with {, anything else should be a csv header
+    let is_json = reader.fill_buf()?.first() == Some(&b'{');
+    let rows: Box<dyn Iterator<Item = Result<UnprocessedTransaction, ParseError>>> = if is_json {
+        Box::new(read_transactions_from_json_lines(
+            reader,
+            source,
+            scales.clone(),
+        ))
+    } else {
+        Box::new(read_transactions_from_csv(reader, source, scales.clone()))
+    };
+
+    for row in rows {
+        requests.send(match row {
+            Ok(transaction) => Request::Transaction(transaction),
+            Err(err) => Request::Invalid(err),
+        })?;
+    }
+
+    Ok(())
+}
+
+fn accept_queries(listener: TcpListener, requests: SyncSender<Request>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        let requests = requests.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_query(stream, &requests) {
+                eprintln!("Query failed: {err}");
+            }
+        });
+    }
+}
+
+fn handle_query(stream: TcpStream, requests: &SyncSender<Request>) -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let mut stream = stream;
+
+    let query = match line.parse::<Query>() {
+        Ok(query) => query,
+        Err(err) => {
+            stream.write_all(format!("error: {err}\n").as_bytes())?;
+            return Ok(());
+        }
+    };
+
+    if query == Query::Shutdown {
+        // reply first, the process exits once the final balances are written
+        stream.write_all(b"ok\n")?;
+        stream.flush()?;
+        requests.send(Request::Shutdown)?;
+        return Ok(());
+    }
+
+    let (reply, response) = mpsc::channel();
+    requests.send(Request::Query(query, reply))?;
+    stream.write_all(&response.recv()?)?;
+
+    Ok(())
+}
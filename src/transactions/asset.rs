@@ -0,0 +1,54 @@
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::str::FromStr;
+
+const MAX_ASSET_LENGTH: usize = 16;
+
+// a short asset code such as USD or BTC, stored inline so transactions stay Copy
+// the default (empty) asset is used when the input doesn't specify one
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Asset([u8; MAX_ASSET_LENGTH]);
+
+impl Asset {
+    pub fn is_default(&self) -> bool {
+        *self == Asset::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        let length = self
+            .0
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(MAX_ASSET_LENGTH);
+        // only ascii is ever stored
+        std::str::from_utf8(&self.0[..length]).unwrap_or_default()
+    }
+}
+
+impl FromStr for Asset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.len() > MAX_ASSET_LENGTH {
+            return Err(anyhow!(
+                "Asset code longer than {MAX_ASSET_LENGTH} characters: {s}"
+            ));
+        }
+        if !s
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+        {
+            return Err(anyhow!("Invalid asset code: {s}"));
+        }
+
+        let mut bytes = [0; MAX_ASSET_LENGTH];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Asset(bytes))
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -1,2 +1,10 @@
+pub mod asset;
+pub use asset::Asset;
+
 pub mod transaction;
-pub use transaction::{Transaction, TransactionType, UnprocessedTransaction};
+pub use transaction::{
+    Amount, DisputeState, FundsType, Transaction, TransactionType, UnprocessedTransaction,
+};
+
+pub mod transaction_error;
+pub use transaction_error::TransactionError;
@@ -1,5 +1,30 @@
+use crate::transactions::Asset;
 use crate::util::Fixed;
 
+// amounts and balances in every asset, held as a whole number of the asset's smallest unit, see AssetScales
+// parsing and displaying an Amount itself uses 4 decimal places, which is only right for assets with the default
+pub type Amount = Fixed;
+
+// the direction funds move in, amounts are always stored as positive values
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FundsType {
+    #[default]
+    Deposit,
+    Withdrawal,
+}
+
+// where a transaction is in its dispute lifecycle, each state records the chronology of the claim which caused it
+//  Normal -> Disputed -> Resolved (-> Disputed again, if re-disputes are allowed)
+//                     -> ChargedBack
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum DisputeState {
+    #[default]
+    Normal,
+    Disputed(u64),
+    Resolved(u64),
+    ChargedBack(u64),
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Transaction {
     // id is unique, but does not specify ordering
@@ -7,8 +32,11 @@ pub struct Transaction {
     // chronology may not be unique (can imagine it being a timestamp), but does specify ordering
     // in the case of disputed chronology (2 conflicting transactions happening at the same time), id will be used to order
     pub chronology: u64,
-    pub amount: Fixed,
-    pub disputed: bool,
+    pub funds_type: FundsType,
+    // disputes move funds in the asset of the original transaction
+    pub asset: Asset,
+    pub amount: Amount,
+    pub dispute_state: DisputeState,
 }
 
 impl PartialOrd for Transaction {
@@ -24,12 +52,20 @@ impl PartialEq for Transaction {
 }
 
 impl Transaction {
-    pub fn new(id: u64, chronology: u64, amount: Fixed) -> Self {
+    pub fn new(
+        id: u64,
+        chronology: u64,
+        funds_type: FundsType,
+        asset: Asset,
+        amount: Amount,
+    ) -> Self {
         Self {
             id,
             chronology,
+            funds_type,
+            asset,
             amount,
-            disputed: false,
+            dispute_state: DisputeState::Normal,
         }
     }
 }
@@ -41,46 +77,34 @@ pub struct Claim {
     pub claim_type: ClaimType,
 }
 
-// TODO implement these as the same type
 #[derive(Debug, Clone, Copy)]
-pub struct Dispute {
-    pub id: u64,
-    pub chronology: u64,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct Resolve {
-    pub id: u64,
-    pub chronology: u64,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct Chargeback {
-    pub id: u64,
-    pub chronology: u64,
-}
-
-#[derive(Debug)]
 pub struct TransactionMetadata {
     pub client_id: u64,
     pub transaction_id: u64,
     pub chronology: u64,
+    // the input row, the same as chronology unless the input specifies its own ordering
+    pub row: u64,
+    // index of the input the transaction was read from, rows are numbered per input
+    pub source: u32,
+    // only meaningful for deposits and withdrawals, claims use the asset of the transaction they reference
+    pub asset: Asset,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ClaimType {
     Dispute,
     Resolve,
     Chargeback,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum TransactionType {
-    UpdateFunds(Fixed),
+    Deposit(Amount),
+    Withdrawal(Amount),
     Claim(ClaimType),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct UnprocessedTransaction {
     pub transaction_type: TransactionType,
     pub metadata: TransactionMetadata,
@@ -92,6 +116,7 @@ impl UnprocessedTransaction {
         client_id: u64,
         transaction_id: u64,
         chronology: u64,
+        asset: Asset,
     ) -> Self {
         Self {
             transaction_type,
@@ -99,7 +124,67 @@ impl UnprocessedTransaction {
                 client_id,
                 transaction_id,
                 chronology,
+                row: chronology,
+                source: 0,
+                asset,
             },
         }
     }
+
+    // the following start with the default asset and a chronology of 0
+    // chronology orders transactions for claims and locking, so anything applying transactions itself
+    // should give each a later chronology than the last, e.g. a sequence number
+    pub fn deposit(client_id: u64, transaction_id: u64, amount: Amount) -> Self {
+        Self::new(
+            TransactionType::Deposit(amount),
+            client_id,
+            transaction_id,
+            0,
+            Asset::default(),
+        )
+    }
+
+    pub fn withdrawal(client_id: u64, transaction_id: u64, amount: Amount) -> Self {
+        Self::new(
+            TransactionType::Withdrawal(amount),
+            client_id,
+            transaction_id,
+            0,
+            Asset::default(),
+        )
+    }
+
+    pub fn dispute(client_id: u64, transaction_id: u64) -> Self {
+        Self::claim(ClaimType::Dispute, client_id, transaction_id)
+    }
+
+    pub fn resolve(client_id: u64, transaction_id: u64) -> Self {
+        Self::claim(ClaimType::Resolve, client_id, transaction_id)
+    }
+
+    pub fn chargeback(client_id: u64, transaction_id: u64) -> Self {
+        Self::claim(ClaimType::Chargeback, client_id, transaction_id)
+    }
+
+    fn claim(claim_type: ClaimType, client_id: u64, transaction_id: u64) -> Self {
+        Self::new(
+            TransactionType::Claim(claim_type),
+            client_id,
+            transaction_id,
+            0,
+            Asset::default(),
+        )
+    }
+
+    pub fn with_chronology(mut self, chronology: u64) -> Self {
+        self.metadata.chronology = chronology;
+        self.metadata.row = chronology;
+        self
+    }
+
+    // claims ignore their asset, they use the asset of the transaction they refer to
+    pub fn with_asset(mut self, asset: Asset) -> Self {
+        self.metadata.asset = asset;
+        self
+    }
 }
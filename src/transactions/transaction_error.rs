@@ -0,0 +1,51 @@
+use std::fmt;
+
+// reasons a transaction can be rejected
+// these are not system errors, the transaction is ignored and processing continues
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionError {
+    // the account was locked by an earlier chargeback
+    AccountLocked,
+    // a withdrawal would take available funds below 0
+    InsufficientFunds,
+    // a claim referenced a transaction which doesn't exist (or was rejected)
+    UnknownTransaction,
+    // a dispute referenced a transaction which is already disputed
+    AlreadyDisputed,
+    // a resolve or chargeback referenced a transaction which isn't disputed
+    NotDisputed,
+    // a dispute referenced a transaction whose dispute has already been charged back,
+    // or resolved when re-disputes aren't allowed
+    DisputeSettled,
+    // a deposit or withdrawal reused a transaction id
+    DuplicateTransactionId,
+    // a claim referenced a transaction belonging to a different client
+    ClientMismatch,
+    // applying the transaction would overflow a balance
+    Overflow,
+    // the client id is larger than the client store supports
+    ClientIdOutOfRange,
+    // the transaction arrived after later transactions had already left the reorder window
+    LateArrival,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            TransactionError::AccountLocked => "account locked",
+            TransactionError::InsufficientFunds => "insufficient funds",
+            TransactionError::UnknownTransaction => "unknown transaction",
+            TransactionError::AlreadyDisputed => "transaction already disputed",
+            TransactionError::NotDisputed => "transaction not disputed",
+            TransactionError::DisputeSettled => "dispute already settled",
+            TransactionError::DuplicateTransactionId => "duplicate transaction id",
+            TransactionError::ClientMismatch => "transaction belongs to a different client",
+            TransactionError::Overflow => "balance overflow",
+            TransactionError::ClientIdOutOfRange => "client id out of range",
+            TransactionError::LateArrival => "arrived too late to be reordered",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+impl std::error::Error for TransactionError {}
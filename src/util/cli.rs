@@ -1,13 +1,186 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+
+use crate::io::asset_scales::AssetScale;
+use crate::io::serialized_client::AccountsLayout;
+use crate::io::write_ahead_log::SyncPolicy;
+use crate::io::{InputFormat, OutputFormat, ParseErrorPolicy};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Cli {
-    pub csv_filepath: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Files to read transactions from, merged in chronology order, - reads from stdin
+    #[arg(required = true)]
+    pub filepaths: Vec<String>,
+    /// How transactions are encoded, by default picked by file extension: .jsonl or .ndjson for json lines, .bin for binary logs, otherwise csv
+    #[arg(long, value_enum)]
+    pub input_format: Option<InputFormat>,
+    /// Decimal places of an asset's amounts, e.g. BTC=8, repeated for each asset, anything else has 4
+    #[arg(long = "asset-scale", value_name = "ASSET=N")]
+    pub asset_scales: Vec<AssetScale>,
+    /// Write balances to this file rather than stdout
+    #[arg(long)]
+    pub output: Option<String>,
+    /// How to encode balances, by default picked by the output's extension: .json, .jsonl, .ndjson or .parquet, otherwise csv
+    #[arg(long, value_enum)]
+    pub output_format: Option<OutputFormat>,
+    /// Write every rejected transaction and the reason it was rejected to this csv file
+    #[arg(long)]
+    pub rejections: Option<String>,
+    /// What to do with rows which fail to parse: strict, skip or skip-with-limit=N
+    #[arg(long, default_value = "strict")]
+    pub on_parse_error: ParseErrorPolicy,
+    /// How to lay out balances when clients hold multiple assets
+    #[arg(long, value_enum, default_value_t)]
+    pub layout: AccountsLayout,
+    /// Store clients in a vec indexed by client id, faster for small dense ids but rejects ids above 65535
+    #[arg(long)]
+    pub dense_clients: bool,
+    /// Reject disputes of transactions whose earlier dispute has been resolved
+    #[arg(long)]
+    pub no_redispute: bool,
+    /// How many transactions to hold back to put them in timestamp/seq order
+    #[arg(long, default_value_t = 0)]
+    pub reorder_window: usize,
+    /// How many threads to apply transactions on, clients are sharded between them by client id
+    #[arg(long, default_value_t = 1)]
+    pub threads: usize,
+    /// Append every applied transaction to this log, and if it already exists rebuild balances from it then skip the input it covers
+    #[arg(long)]
+    pub wal: Option<String>,
+    /// When to sync the write-ahead log to disk: records=N every N transactions, or ms=T on the first transaction appended T milliseconds after the last sync
+    #[arg(long, default_value = "records=1000", requires = "wal")]
+    pub wal_sync: SyncPolicy,
+    /// Start from the state saved in this snapshot, the input is then applied after everything in it
+    #[arg(long)]
+    pub restore: Option<String>,
+    /// Start clients with the balances in this accounts csv (in the long layout) rather than at zero
+    #[arg(long, conflicts_with = "restore")]
+    pub opening_balances: Option<String>,
+    /// Once every transaction has been applied, save the state to this snapshot so a later run can carry on from it
+    #[arg(long)]
+    pub snapshot: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Apply transactions streamed over tcp, answering balance queries until shut down
+    Serve(ServeArgs),
+    /// Convert transactions to a binary log, which is much faster to read again
+    Convert(ConvertArgs),
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// File to read transactions from, - reads from stdin
+    pub input: String,
+    /// Binary log to write
+    pub output: String,
+    /// How transactions are encoded, by default picked by file extension: .jsonl or .ndjson for json lines, otherwise csv
+    #[arg(long, value_enum)]
+    pub input_format: Option<InputFormat>,
+    /// What to do with rows which fail to parse: strict, skip or skip-with-limit=N
+    #[arg(long, default_value = "strict")]
+    pub on_parse_error: ParseErrorPolicy,
+    /// Decimal places of an asset's amounts, e.g. BTC=8, repeated for each asset, anything else has 4
+    #[arg(long = "asset-scale", value_name = "ASSET=N")]
+    pub asset_scales: Vec<AssetScale>,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to accept csv or newline delimited json transaction streams on
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    pub listen: String,
+    /// Address to answer queries on, one per connection: client <id>, all or shutdown
+    #[arg(long, default_value = "127.0.0.1:7879")]
+    pub query: String,
+    /// Write every rejected transaction and the reason it was rejected to this csv file
+    #[arg(long)]
+    pub rejections: Option<String>,
+    /// Decimal places of an asset's amounts, e.g. BTC=8, repeated for each asset, anything else has 4
+    #[arg(long = "asset-scale", value_name = "ASSET=N")]
+    pub asset_scales: Vec<AssetScale>,
+    /// How to lay out balances when clients hold multiple assets
+    #[arg(long, value_enum, default_value_t)]
+    pub layout: AccountsLayout,
+    /// How to encode balances, for queries and the final balances
+    #[arg(long, value_enum, default_value_t)]
+    pub output_format: OutputFormat,
+    /// Reject disputes of transactions whose earlier dispute has been resolved
+    #[arg(long)]
+    pub no_redispute: bool,
 }
 
 impl Cli {
     pub fn from_args() -> Self {
         Cli::parse()
     }
+
+    // options which only work on a single engine, which more than one thread doesn't have
+    // checked after parsing rather than with conflicts_with, so an explicit --threads 1 is still allowed
+    pub fn single_threaded_options(&self) -> Vec<&'static str> {
+        [
+            ("--wal", self.wal.is_some()),
+            ("--restore", self.restore.is_some()),
+            ("--opening-balances", self.opening_balances.is_some()),
+            ("--snapshot", self.snapshot.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(option, _)| option)
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(["kraken", "input.csv"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn single_threaded_options_allow_one_thread() {
+        assert_eq!(
+            parse(&["--threads", "1", "--wal", "log.bin"]).single_threaded_options(),
+            vec!["--wal"]
+        );
+        assert_eq!(
+            parse(&[
+                "--threads",
+                "1",
+                "--restore",
+                "a.bin",
+                "--snapshot",
+                "b.bin"
+            ])
+            .single_threaded_options(),
+            vec!["--restore", "--snapshot"]
+        );
+        assert_eq!(
+            parse(&["--threads", "1", "--opening-balances", "accounts.csv"])
+                .single_threaded_options(),
+            vec!["--opening-balances"]
+        );
+        assert!(
+            Cli::try_parse_from([
+                "kraken",
+                "input.csv",
+                "--restore",
+                "a.bin",
+                "--opening-balances",
+                "accounts.csv"
+            ])
+            .is_err()
+        );
+        assert!(
+            parse(&["--threads", "4"])
+                .single_threaded_options()
+                .is_empty()
+        );
+    }
 }
@@ -1,63 +1,242 @@
 use anyhow::{Error, Result, anyhow};
-use num::{CheckedAdd, CheckedSub, Num, One, Signed, Zero};
+use num::bigint::BigInt;
+use num::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, Num, One, Signed, ToPrimitive, Zero};
 use serde::Deserialize;
 use std::fmt;
 use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
 use std::str::FromStr;
 
+// a decimal number with a fixed number of decimal places, stored as an integer number of the smallest unit
+// e.g. Fixed<4> (the default, used by the csv path) stores 1.05 as 10_500 units of 0.0001
+// crypto assets can use Fixed<8> or Fixed<18>, i128 leaves room for ~1.7e20 at 18 decimal places
 #[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, PartialOrd)]
-pub struct Fixed(i128);
+pub struct Fixed<const DECIMAL_PLACES: u32 = 4>(i128);
+
+// how to round a result which can't be represented in the available decimal places
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+    // truncate
+    TowardZero,
+    AwayFromZero,
+    // round towards negative infinity
+    Floor,
+    // round towards positive infinity
+    Ceiling,
+    // ties round away from zero
+    HalfUp,
+    // ties round to the nearest even number (banker's rounding)
+    #[default]
+    HalfEven,
+}
 
-impl FromStr for Fixed {
-    type Err = anyhow::Error;
+impl<const DECIMAL_PLACES: u32> Fixed<DECIMAL_PLACES> {
+    pub const DECIMAL_PLACES: u32 = DECIMAL_PLACES;
+    // the number of raw units in 1, fails to compile for more than 38 decimal places
+    const SCALE: i128 = 10i128.pow(DECIMAL_PLACES);
 
-    fn from_str(s: &str) -> Result<Self> {
-        let parts: Vec<&str> = s.split(".").collect();
-
-        // ensure we only have 1 decimal point
-        match parts[..] {
-            [integer_str, fraction_str] => {
-                let integer = integer_str.parse::<i128>().map_err(|err| {
-                    anyhow!("Invalid integer part of fixed point value {s}: {err}")
-                })?;
-                let fraction = fraction_str.parse::<u32>().map_err(|err| {
-                    anyhow!("Invalid fractional part of fixed point value {s}: {err}")
-                })?;
-
-                let integer_x_10_000 = integer
-                    .checked_mul(10_000)
-                    .ok_or(anyhow!("Integer part of fixed point value to large: {s}"))?;
-
-                // handle trailing 0s
-                let fraction_x_10_000 = if fraction < 10 {
-                    Ok(fraction * 1_000)
-                } else if fraction < 100 {
-                    Ok(fraction * 100)
-                } else if fraction < 1_000 {
-                    Ok(fraction * 10)
-                } else if fraction < 10_000 {
-                    Ok(fraction)
-                } else {
-                    Err(anyhow!(
-                        "Fractional part of fixed point value to large: {s}"
-                    ))
-                }?;
-
-                Ok(Fixed(integer_x_10_000 + fraction_x_10_000 as i128))
+    // the raw value in units of the smallest decimal place
+    pub fn from_raw(raw: i128) -> Self {
+        Fixed(raw)
+    }
+
+    pub fn raw(&self) -> i128 {
+        self.0
+    }
+
+    pub fn checked_mul_rounded(&self, rhs: &Self, rounding: RoundingMode) -> Option<Self> {
+        // (a / SCALE) * (b / SCALE) = (a * b / SCALE) / SCALE
+        // the intermediate product can exceed i128, so it is calculated in a BigInt
+        divide_rounded(
+            BigInt::from(self.0) * BigInt::from(rhs.0),
+            BigInt::from(Self::SCALE),
+            rounding,
+        )
+        .map(Fixed)
+    }
+
+    pub fn checked_div_rounded(&self, rhs: &Self, rounding: RoundingMode) -> Option<Self> {
+        if rhs.is_zero() {
+            return None;
+        }
+
+        // (a / SCALE) / (b / SCALE) = (a * SCALE / b) / SCALE
+        divide_rounded(
+            BigInt::from(self.0) * BigInt::from(Self::SCALE),
+            BigInt::from(rhs.0),
+            rounding,
+        )
+        .map(Fixed)
+    }
+
+    // converts to a different number of decimal places, failing if precision would be lost or the value overflows
+    pub fn rescale<const TO: u32>(&self) -> Result<Fixed<TO>> {
+        if TO >= DECIMAL_PLACES {
+            10i128
+                .checked_pow(TO - DECIMAL_PLACES)
+                .and_then(|factor| self.0.checked_mul(factor))
+                .map(Fixed)
+                .ok_or(anyhow!(
+                    "Fixed point value {self} too large for {TO} decimal places"
+                ))
+        } else {
+            let factor = 10i128.pow(DECIMAL_PLACES - TO);
+            if self.0 % factor != 0 {
+                return Err(anyhow!(
+                    "Fixed point value {self} has more than {TO} decimal places"
+                ));
             }
-            _ => Err(anyhow!("Invalid fixed point value: {s}")),
+            Ok(Fixed(self.0 / factor))
+        }
+    }
+
+    // converts to fewer decimal places, rounding away the extra precision
+    pub fn rescale_rounded<const TO: u32>(&self, rounding: RoundingMode) -> Option<Fixed<TO>> {
+        if TO >= DECIMAL_PLACES {
+            return self.rescale().ok();
         }
+
+        divide_rounded(
+            BigInt::from(self.0),
+            BigInt::from(10i128.pow(DECIMAL_PLACES - TO)),
+            rounding,
+        )
+        .map(Fixed)
     }
 }
 
-impl fmt::Display for Fixed {
+// divides and rounds, returning None if the result doesn't fit in an i128
+fn divide_rounded(numerator: BigInt, denominator: BigInt, rounding: RoundingMode) -> Option<i128> {
+    let quotient = &numerator / &denominator;
+    let remainder = &numerator % &denominator;
+
+    let quotient = if remainder.is_zero() {
+        quotient
+    } else {
+        // the direction to round away from zero in
+        let sign = if numerator.is_negative() != denominator.is_negative() {
+            -BigInt::one()
+        } else {
+            BigInt::one()
+        };
+        let twice_remainder = remainder.abs() * 2;
+        let away_from_zero = match rounding {
+            RoundingMode::TowardZero => false,
+            RoundingMode::AwayFromZero => true,
+            RoundingMode::Floor => sign.is_negative(),
+            RoundingMode::Ceiling => sign.is_positive(),
+            RoundingMode::HalfUp => twice_remainder >= denominator.abs(),
+            RoundingMode::HalfEven => {
+                twice_remainder > denominator.abs()
+                    || (twice_remainder == denominator.abs() && quotient.bit(0))
+            }
+        };
+
+        if away_from_zero {
+            quotient + sign
+        } else {
+            quotient
+        }
+    };
+
+    quotient.to_i128()
+}
+
+impl<const DECIMAL_PLACES: u32> FromStr for Fixed<DECIMAL_PLACES> {
+    type Err = anyhow::Error;
+
+    // accepts an optional sign, then digits with an optional decimal point, such as "5", "-1.05", ".5" or "5."
+    fn from_str(s: &str) -> Result<Self> {
+        parse_decimal(s, DECIMAL_PLACES).map(Fixed)
+    }
+}
+
+// the same as Fixed::from_str, for a number of decimal places only known at runtime
+// returns the raw value in units of the smallest decimal place
+pub fn parse_decimal(s: &str, decimal_places: u32) -> Result<i128> {
+    let (negative, unsigned) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let (integer_str, fraction_str) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    // ensure we only have digits, and at least one of them
+    if integer_str.is_empty() && fraction_str.is_empty() {
+        return Err(anyhow!("Invalid fixed point value: {s}"));
+    }
+    if !integer_str.bytes().all(|b| b.is_ascii_digit())
+        || !fraction_str.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(anyhow!("Invalid fixed point value: {s}"));
+    }
+    if fraction_str.len() > decimal_places as usize {
+        return Err(anyhow!(
+            "Fixed point value has more than {decimal_places} decimal places: {s}"
+        ));
+    }
+    let scale = 10i128
+        .checked_pow(decimal_places)
+        .ok_or_else(|| anyhow!("Fixed point values can't have {decimal_places} decimal places"))?;
+
+    let integer = if integer_str.is_empty() {
+        0
+    } else {
+        integer_str
+            .parse::<i128>()
+            .map_err(|err| anyhow!("Invalid integer part of fixed point value {s}: {err}"))?
+    };
+
+    // handle trailing 0s by padding the fraction out to decimal_places digits
+    let fraction = if decimal_places == 0 {
+        0
+    } else {
+        format!("{fraction_str:0<width$}", width = decimal_places as usize)
+            .parse::<i128>()
+            .map_err(|err| anyhow!("Invalid fractional part of fixed point value {s}: {err}"))?
+    };
+
+    let raw = integer
+        .checked_mul(scale)
+        .and_then(|integer_x_scale| integer_x_scale.checked_add(fraction))
+        .ok_or_else(|| anyhow!("Fixed point value too large: {s}"))?;
+
+    Ok(if negative { -raw } else { raw })
+}
+
+impl<const DECIMAL_PLACES: u32> fmt::Display for Fixed<DECIMAL_PLACES> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}.{}", self.0 / 10_000, self.0 % 10_000)
+        write_decimal(f, self.0, DECIMAL_PLACES)
+    }
+}
+
+// the same as Fixed's Display, for a number of decimal places only known at runtime
+pub fn format_decimal(raw: i128, decimal_places: u32) -> String {
+    let mut s = String::new();
+    write_decimal(&mut s, raw, decimal_places).expect("Writing to a String can't fail");
+    s
+}
+
+// writes raw units of decimal_places, padding the fraction out to all of them
+fn write_decimal(f: &mut impl fmt::Write, raw: i128, decimal_places: u32) -> fmt::Result {
+    // work with the absolute value so the fraction is never negative
+    let sign = if raw.is_negative() { "-" } else { "" };
+    let abs = raw.unsigned_abs();
+    if decimal_places == 0 {
+        return write!(f, "{sign}{abs}");
     }
+    let scale = 10u128.pow(decimal_places);
+    write!(
+        f,
+        "{sign}{}.{:0width$}",
+        abs / scale,
+        abs % scale,
+        width = decimal_places as usize
+    )
 }
 
 // In my opinion CheckedAdd should not require Add to be implemented
-impl Add for Fixed {
+impl<const DECIMAL_PLACES: u32> Add for Fixed<DECIMAL_PLACES> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -65,13 +244,13 @@ impl Add for Fixed {
     }
 }
 
-impl CheckedAdd for Fixed {
+impl<const DECIMAL_PLACES: u32> CheckedAdd for Fixed<DECIMAL_PLACES> {
     fn checked_add(&self, v: &Self) -> Option<Self> {
         self.0.checked_add(v.0).map(Fixed)
     }
 }
 
-impl Sub for Fixed {
+impl<const DECIMAL_PLACES: u32> Sub for Fixed<DECIMAL_PLACES> {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
@@ -79,13 +258,13 @@ impl Sub for Fixed {
     }
 }
 
-impl CheckedSub for Fixed {
+impl<const DECIMAL_PLACES: u32> CheckedSub for Fixed<DECIMAL_PLACES> {
     fn checked_sub(&self, v: &Self) -> Option<Self> {
         self.0.checked_sub(v.0).map(Fixed)
     }
 }
 
-impl Neg for Fixed {
+impl<const DECIMAL_PLACES: u32> Neg for Fixed<DECIMAL_PLACES> {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
@@ -93,31 +272,47 @@ impl Neg for Fixed {
     }
 }
 
-impl Rem for Fixed {
-    type Output = Fixed;
+// both values share a scale, so the remainder of the raw values is already scaled
+impl<const DECIMAL_PLACES: u32> Rem for Fixed<DECIMAL_PLACES> {
+    type Output = Self;
 
     fn rem(self, rhs: Self) -> Self::Output {
         Fixed(self.0.rem(rhs.0))
     }
 }
 
-impl Div for Fixed {
-    type Output = Fixed;
+// the operators round half to even, use checked_div_rounded for other rounding modes
+impl<const DECIMAL_PLACES: u32> Div for Fixed<DECIMAL_PLACES> {
+    type Output = Self;
 
     fn div(self, rhs: Self) -> Self::Output {
-        Fixed(self.0.div(rhs.0))
+        self.checked_div(&rhs)
+            .expect("Division by zero or overflow in fixed point division")
+    }
+}
+
+impl<const DECIMAL_PLACES: u32> CheckedDiv for Fixed<DECIMAL_PLACES> {
+    fn checked_div(&self, v: &Self) -> Option<Self> {
+        self.checked_div_rounded(v, RoundingMode::default())
     }
 }
 
-impl Mul for Fixed {
-    type Output = Fixed;
+impl<const DECIMAL_PLACES: u32> Mul for Fixed<DECIMAL_PLACES> {
+    type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        Fixed(self.0.mul(rhs.0))
+        self.checked_mul(&rhs)
+            .expect("Overflow in fixed point multiplication")
     }
 }
 
-impl Zero for Fixed {
+impl<const DECIMAL_PLACES: u32> CheckedMul for Fixed<DECIMAL_PLACES> {
+    fn checked_mul(&self, v: &Self) -> Option<Self> {
+        self.checked_mul_rounded(v, RoundingMode::default())
+    }
+}
+
+impl<const DECIMAL_PLACES: u32> Zero for Fixed<DECIMAL_PLACES> {
     fn is_zero(&self) -> bool {
         self.0.is_zero()
     }
@@ -131,58 +326,35 @@ impl Zero for Fixed {
     }
 }
 
-impl One for Fixed {
+impl<const DECIMAL_PLACES: u32> One for Fixed<DECIMAL_PLACES> {
     fn is_one(&self) -> bool
     where
         Self: PartialEq,
     {
-        self.0.is_one()
+        self.0 == Self::SCALE
     }
 
     fn one() -> Self {
-        Self(i128::one())
+        Self(Self::SCALE)
     }
 }
 
-impl Num for Fixed {
+impl<const DECIMAL_PLACES: u32> Num for Fixed<DECIMAL_PLACES> {
     type FromStrRadixErr = Error;
 
+    // a fixed number of decimal places only makes sense in base 10
     fn from_str_radix(src: &str, radix: u32) -> Result<Self, <Self as Num>::FromStrRadixErr> {
-        let parts: Vec<&str> = src.split(".").collect();
-
-        // ensure we only have 1 decimal point
-        match parts[..] {
-            [integer_str, fraction_str] => {
-                let integer = i128::from_str_radix(integer_str, radix)?;
-                let fraction = u32::from_str_radix(fraction_str, radix)?;
-
-                let integer_x_10_000 = integer
-                    .checked_mul(10_000)
-                    .ok_or(anyhow!("Integer part of fixed point value to large: {src}"))?;
-
-                // handle trailing 0s
-                let fraction_x_10_000 = if fraction < 10 {
-                    Ok(fraction * 1_000)
-                } else if fraction < 100 {
-                    Ok(fraction * 100)
-                } else if fraction < 1_000 {
-                    Ok(fraction * 10)
-                } else if fraction < 10_000 {
-                    Ok(fraction)
-                } else {
-                    Err(anyhow!(
-                        "Fractional part of fixed point value to large: {src}"
-                    ))
-                }?;
-
-                Ok(Fixed(integer_x_10_000 + fraction_x_10_000 as i128))
-            }
-            _ => Err(anyhow!("Invalid fixed point value: {src}")),
+        if radix != 10 {
+            return Err(anyhow!(
+                "Fixed point values can only be parsed in base 10, not base {radix}: {src}"
+            ));
         }
+
+        Fixed::from_str(src)
     }
 }
 
-impl Signed for Fixed {
+impl<const DECIMAL_PLACES: u32> Signed for Fixed<DECIMAL_PLACES> {
     fn abs(&self) -> Self {
         Fixed(self.0.abs())
     }
@@ -192,7 +364,7 @@ impl Signed for Fixed {
     }
 
     fn signum(&self) -> Self {
-        Fixed(self.0.signum())
+        Fixed(self.0.signum() * Self::SCALE)
     }
 
     fn is_positive(&self) -> bool {
@@ -204,10 +376,177 @@ impl Signed for Fixed {
     }
 }
 
-// impl Fixed {
-//     pub fn zero() -> Self {
-//         Self {
-//             data: 0
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn fixed(s: &str) -> Fixed {
+        Fixed::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parses_accepted_forms() {
+        assert_eq!(fixed("5"), Fixed(50_000));
+        assert_eq!(fixed("5."), Fixed(50_000));
+        assert_eq!(fixed(".5"), Fixed(5_000));
+        assert_eq!(fixed("1.05"), Fixed(10_500));
+        assert_eq!(fixed("1.0001"), Fixed(10_001));
+        assert_eq!(fixed("-0.5"), Fixed(-5_000));
+        assert_eq!(fixed("-.05"), Fixed(-500));
+        assert_eq!(fixed("+2.5"), Fixed(25_000));
+    }
+
+    #[test]
+    fn rejects_invalid_forms() {
+        for s in [
+            "", ".", "-", "1.2.3", "1.00001", "1e5", "--1", "1.-5", " 1", "a",
+        ] {
+            assert!(Fixed::<4>::from_str(s).is_err(), "{s} should not parse");
+        }
+    }
+
+    #[test]
+    fn displays_with_padding() {
+        assert_eq!(fixed("1.05").to_string(), "1.0500");
+        assert_eq!(fixed("0").to_string(), "0.0000");
+        assert_eq!(fixed("-0.05").to_string(), "-0.0500");
+        assert_eq!(fixed("-12.3456").to_string(), "-12.3456");
+    }
+
+    #[test]
+    fn multiplies_and_divides_with_rounding() {
+        assert_eq!(fixed("1.5") * fixed("2"), fixed("3"));
+        assert_eq!(fixed("-1.5") * fixed("0.5"), fixed("-0.75"));
+        assert_eq!(fixed("1") / fixed("4"), fixed("0.25"));
+        assert_eq!(fixed("1") / fixed("3"), fixed("0.3333"));
+        assert_eq!(fixed("2") / fixed("3"), fixed("0.6667"));
+
+        // 0.00005 is exactly half way between 0.0000 and 0.0001
+        let half = (fixed("0.0001"), fixed("2"));
+        let round = |mode| half.0.checked_div_rounded(&half.1, mode).unwrap();
+        assert_eq!(round(RoundingMode::TowardZero), fixed("0"));
+        assert_eq!(round(RoundingMode::AwayFromZero), fixed("0.0001"));
+        assert_eq!(round(RoundingMode::Floor), fixed("0"));
+        assert_eq!(round(RoundingMode::Ceiling), fixed("0.0001"));
+        assert_eq!(round(RoundingMode::HalfUp), fixed("0.0001"));
+        assert_eq!(round(RoundingMode::HalfEven), fixed("0"));
+
+        let negative_half = (fixed("-0.0003"), fixed("2"));
+        let round = |mode| {
+            negative_half
+                .0
+                .checked_div_rounded(&negative_half.1, mode)
+                .unwrap()
+        };
+        assert_eq!(round(RoundingMode::TowardZero), fixed("-0.0001"));
+        assert_eq!(round(RoundingMode::Floor), fixed("-0.0002"));
+        assert_eq!(round(RoundingMode::Ceiling), fixed("-0.0001"));
+        assert_eq!(round(RoundingMode::HalfUp), fixed("-0.0002"));
+        assert_eq!(round(RoundingMode::HalfEven), fixed("-0.0002"));
+
+        assert_eq!(fixed("1").checked_div(&fixed("0")), None);
+        assert_eq!(Fixed(i128::MAX).checked_mul(&fixed("2")), None);
+    }
+
+    #[test]
+    fn supports_other_precisions() {
+        let satoshi = Fixed::<8>::from_str("0.00000001").unwrap();
+        assert_eq!(satoshi.raw(), 1);
+        assert_eq!(satoshi.to_string(), "0.00000001");
+
+        let wei = Fixed::<18>::from_str("-1.000000000000000001").unwrap();
+        assert_eq!(wei.raw(), -1_000_000_000_000_000_001);
+        assert_eq!(wei.to_string(), "-1.000000000000000001");
+
+        let whole = Fixed::<0>::from_str("42").unwrap();
+        assert_eq!(whole.to_string(), "42");
+        assert!(Fixed::<0>::from_str("42.5").is_err());
+
+        assert!(Fixed::<4>::from_str("0.00000001").is_err());
+        assert_eq!(
+            Fixed::<8>::from_str("1.5").unwrap() * Fixed::<8>::from_str("0.00000003").unwrap(),
+            Fixed::<8>::from_str("0.00000004").unwrap()
+        );
+    }
+
+    #[test]
+    fn rescales_losslessly() {
+        let value = fixed("1.05");
+        let rescaled: Fixed<8> = value.rescale().unwrap();
+        assert_eq!(rescaled.to_string(), "1.05000000");
+        assert_eq!(rescaled.rescale::<4>().unwrap(), value);
+        assert_eq!(rescaled.rescale::<2>().unwrap().to_string(), "1.05");
+        assert!(rescaled.rescale::<1>().is_err());
+        assert_eq!(
+            rescaled.rescale_rounded::<1>(RoundingMode::HalfEven),
+            Some(Fixed::<1>::from_str("1.0").unwrap())
+        );
+        assert!(Fixed::<0>::from_raw(i128::MAX).rescale::<18>().is_err());
+    }
+
+    #[test]
+    fn parses_and_displays_at_runtime_decimal_places() {
+        assert_eq!(parse_decimal("0.00000001", 8).unwrap(), 1);
+        assert_eq!(format_decimal(1, 8), "0.00000001");
+        assert_eq!(parse_decimal("-2.5", 4).unwrap(), -25_000);
+        assert_eq!(format_decimal(-25_000, 4), "-2.5000");
+        assert_eq!(format_decimal(parse_decimal("7", 0).unwrap(), 0), "7");
+
+        let err = parse_decimal("1.00001", 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Fixed point value has more than 4 decimal places: 1.00001"
+        );
+        assert!(parse_decimal("1", 39).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn display_round_trips(raw in any::<i128>().prop_filter("negatable", |raw| *raw != i128::MIN)) {
+            let value = Fixed::<4>(raw);
+            prop_assert_eq!(Fixed::from_str(&value.to_string()).unwrap(), value);
+        }
+
+        #[test]
+        fn parse_round_trips(integer in -1_000_000_000i64..1_000_000_000, fraction in 0u32..10_000) {
+            let s = format!("{integer}.{fraction:04}");
+            prop_assert_eq!(Fixed::<4>::from_str(&s).unwrap().to_string(), s);
+        }
+
+        #[test]
+        fn rescale_round_trips(raw in any::<i64>()) {
+            let value = Fixed::<4>(raw as i128);
+            let up: Fixed<18> = value.rescale().unwrap();
+            prop_assert_eq!(up.rescale::<4>().unwrap(), value);
+            prop_assert_eq!(Fixed::<18>::from_str(&value.to_string()).unwrap(), up);
+        }
+
+        #[test]
+        fn multiplying_by_one_is_identity(raw in any::<i64>()) {
+            let value = Fixed::<4>(raw as i128);
+            prop_assert_eq!(value * Fixed::one(), value);
+            prop_assert_eq!(value / Fixed::one(), value);
+        }
+
+        #[test]
+        fn multiplication_matches_integers(a in -1_000_000i64..1_000_000, b in -1_000_000i64..1_000_000) {
+            let scale = Fixed::<4>::SCALE;
+            let product = Fixed::<4>(a as i128 * scale) * Fixed(b as i128 * scale);
+            prop_assert_eq!(product, Fixed(a as i128 * b as i128 * scale));
+        }
+
+        #[test]
+        fn division_rounding_brackets_exact_result(a in any::<i64>(), b in any::<i64>().prop_filter("non-zero", |b| *b != 0)) {
+            let (a, b) = (Fixed::<8>(a as i128), Fixed::<8>(b as i128));
+            let floor = a.checked_div_rounded(&b, RoundingMode::Floor).unwrap();
+            let ceiling = a.checked_div_rounded(&b, RoundingMode::Ceiling).unwrap();
+            prop_assert!(floor <= ceiling);
+            prop_assert!(ceiling.0 - floor.0 <= 1);
+            for mode in [RoundingMode::TowardZero, RoundingMode::AwayFromZero, RoundingMode::HalfUp, RoundingMode::HalfEven] {
+                let rounded = a.checked_div_rounded(&b, mode).unwrap();
+                prop_assert!(rounded == floor || rounded == ceiling);
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
-pub mod cli;
-pub use cli::Cli;
-
-pub mod fixed;
-pub use fixed::Fixed;
-
-pub mod merge_in_place;
-pub use merge_in_place::merge_in_place;
+pub mod cli;
+pub use cli::Cli;
+
+pub mod fixed;
+pub use fixed::Fixed;
+
+pub mod merge_in_place;
+pub use merge_in_place::merge_in_place;
+
+pub mod varint;
@@ -0,0 +1,33 @@
+// LEB128 variable length integers, small values take a single byte
+// signed values are zigzag encoded first, so small negative values stay small too
+
+pub fn write_varint(bytes: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+// None if the bytes run out, or there are more than a u128 can hold
+pub fn read_varint(bytes: &[u8], at: &mut usize) -> Option<u128> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let byte = *bytes.get(*at)?;
+        *at += 1;
+        value |= ((byte & 0x7f) as u128).checked_shl(shift)?;
+        if byte < 0x80 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+pub fn write_signed_varint(bytes: &mut Vec<u8>, value: i128) {
+    write_varint(bytes, ((value << 1) ^ (value >> 127)) as u128);
+}
+
+pub fn read_signed_varint(bytes: &[u8], at: &mut usize) -> Option<i128> {
+    let zigzag = read_varint(bytes, at)?;
+    Some((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128))
+}
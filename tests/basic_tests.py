@@ -1,4 +1,26 @@
+import os
 import subprocess
+import tempfile
+
+def read_csv_lines(text):
+    # split into lines, then split each line by comma, then remove whitespace from elements
+    return [[x.strip() for x in line.split(',')] for line in text.splitlines()]
+
+def compare(name, output, expected_output):
+    # check output matches expected output (if there is a difference in length, tests will error out)
+    success = True
+    for i in range(0, max(len(output), len(expected_output))):
+        # This doesn't check for capitalisation or for floating point decimal places
+        if output[i] != expected_output[i]:
+            success = False
+            print(f"Difference found in {name} on line {i}")
+            print(f"Output:   {output[i]}")
+            print(f"Expected: {expected_output[i]}")
+    return success
+
+def find_file(directory, names):
+    # the first of names which exists, its extension decides the format
+    return next(os.path.join(directory, name) for name in names if os.path.exists(os.path.join(directory, name)))
 
 def main():
     test_list = [
@@ -6,30 +28,74 @@ def main():
         "withdrawal",
         "resolve",
         "chargeback",
+        "insufficient_funds",
+        "disputed_withdrawal",
+        "disputed_withdrawal_resolve",
+        "disputed_withdrawal_chargeback",
+        "disputed_spent_deposit",
+        "rejections",
+        "parse_errors_skip",
+        "amount_formats",
+        "multi_asset_long",
+        "multi_asset_wide",
+        "sparse_clients",
+        "dense_clients",
+        "dispute_lookup",
+        "cross_client",
+        "dispute_orderings",
+        "redispute_allowed",
+        "redispute_forbidden",
+        "reorder_window",
+        "reorder_window_zero",
+        "sharded",
+        "sharded_reorder",
+        "multiple_sources",
+        "multiple_sources_timestamps",
+        "json_lines_input",
+        "json_output",
+        "ndjson_output_wide",
+        "restore_snapshot",
+        "opening_balances",
+        "asset_scales",
+        "large_amounts",
     ]
     # for each test
     for test in test_list:
         print(f"Running test: {test}")
         # prepend the data directory
-        input_filepath = "tests/data/" + test + "/input.csv"
-        expected_output_filepath = "tests/data/" + test + "/expected_output.csv"
-        # run the Rust code and collect stdout
-        output = subprocess.check_output(['cargo', 'run', '--', input_filepath])
-        # split into lines, then split each line by comma, then remove whitespace from elements
-        output = [[x.strip() for x in line.split(',')] for line in output.decode("utf-8").splitlines()]
-        # read into lines, then split each line by comma, then remove whitespace from elements
-        with open(expected_output_filepath, mode="r", newline="") as f:
-            expected_output = [[x.strip() for x in line.split(',')] for line in f.readlines()]
-        # check output matches expected output (if there is a difference in length, tests will error out)
-        success = True
-        for i in range(0, max(len(output), len(expected_output))):
-            # This doesn't check for capitalisation or for floating point decimal places
-            if output[i] != expected_output[i]:
-                success = False
-                print(f"Difference found on line {i}")
-                print(f"Output:   {output[i]}")
-                print(f"Expected: {expected_output[i]}")
-        
+        test_directory = "tests/data/" + test
+        input_filepath = find_file(test_directory, ["input.csv", "input.jsonl"])
+        expected_output_filepath = find_file(test_directory, ["expected_output.csv", "expected_output.json", "expected_output.ndjson"])
+        # rejections are only checked if the test specifies them
+        expected_rejections_filepath = test_directory + "/expected_rejections.csv"
+        check_rejections = os.path.exists(expected_rejections_filepath)
+        # extra command line arguments are optional
+        args_filepath = test_directory + "/args.txt"
+        args = []
+        if os.path.exists(args_filepath):
+            with open(args_filepath, mode="r") as f:
+                args = f.read().split()
+
+        with tempfile.TemporaryDirectory() as temp_directory:
+            rejections_filepath = os.path.join(temp_directory, "rejections.csv")
+            command = ['cargo', 'run', '--', input_filepath] + args
+            if check_rejections:
+                command += ['--rejections', rejections_filepath]
+
+            # run the Rust code and collect stdout
+            output = subprocess.check_output(command)
+            output = read_csv_lines(output.decode("utf-8"))
+            with open(expected_output_filepath, mode="r", newline="") as f:
+                expected_output = read_csv_lines(f.read())
+            success = compare("output", output, expected_output)
+
+            if check_rejections:
+                with open(rejections_filepath, mode="r", newline="") as f:
+                    rejections = read_csv_lines(f.read())
+                with open(expected_rejections_filepath, mode="r", newline="") as f:
+                    expected_rejections = read_csv_lines(f.read())
+                success = compare("rejections", rejections, expected_rejections) and success
+
         if success:
             print("Test passed")
         else:
@@ -37,4 +103,4 @@ def main():
 
 
 if __name__ == "__main__":
-    main()
\ No newline at end of file
+    main()
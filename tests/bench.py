@@ -0,0 +1,69 @@
+import argparse
+import os
+import random
+import subprocess
+import tempfile
+import time
+
+
+# writes a csv of random deposits, withdrawals and claims spread across many clients
+def generate(filepath, rows, clients, seed):
+    rng = random.Random(seed)
+    # transaction ids of each client's deposits, which claims refer to
+    deposits = [[] for _ in range(clients)]
+    with open(filepath, mode="w") as f:
+        f.write("type,client,tx,amount\n")
+        for tx in range(1, rows + 1):
+            client = rng.randrange(clients)
+            roll = rng.random()
+            if roll < 0.6 or not deposits[client]:
+                f.write(f"deposit,{client},{tx},{rng.randrange(1, 100000) / 100}\n")
+                deposits[client].append(tx)
+            elif roll < 0.9:
+                f.write(f"withdrawal,{client},{tx},{rng.randrange(1, 10000) / 100}\n")
+            else:
+                claim = rng.choice(["dispute", "dispute", "resolve", "chargeback"])
+                f.write(f"{claim},{client},{rng.choice(deposits[client])},\n")
+
+
+def time_run(binary, input_filepath, threads, repeats):
+    best = None
+    output = None
+    for _ in range(repeats):
+        start = time.perf_counter()
+        output = subprocess.check_output([binary, input_filepath, "--threads", str(threads)])
+        elapsed = time.perf_counter() - start
+        best = elapsed if best is None else min(best, elapsed)
+    return best, output
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Times the engine on a generated file at different thread counts")
+    parser.add_argument("--rows", type=int, default=5_000_000)
+    parser.add_argument("--clients", type=int, default=10_000)
+    parser.add_argument("--threads", type=int, nargs="+", default=[1, 2, 4, 8])
+    parser.add_argument("--repeats", type=int, default=3)
+    parser.add_argument("--seed", type=int, default=0)
+    args = parser.parse_args()
+
+    subprocess.check_call(["cargo", "build", "--release"])
+    binary = os.path.join("target", "release", "kraken")
+
+    with tempfile.TemporaryDirectory() as temp_directory:
+        input_filepath = os.path.join(temp_directory, "input.csv")
+        print(f"Generating {args.rows} rows across {args.clients} clients")
+        generate(input_filepath, args.rows, args.clients, args.seed)
+
+        baseline = None
+        baseline_output = None
+        for threads in args.threads:
+            elapsed, output = time_run(binary, input_filepath, threads, args.repeats)
+            if baseline is None:
+                baseline, baseline_output = elapsed, output
+            # every thread count has to agree with the first
+            matches = "identical" if output == baseline_output else "DIFFERENT OUTPUT"
+            print(f"threads={threads}: {elapsed:.2f}s ({baseline / elapsed:.2f}x, {matches})")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,114 @@
+import re
+import socket
+import subprocess
+import tempfile
+import time
+import os
+
+
+def send(address, data):
+    with socket.create_connection(address) as connection:
+        connection.sendall(data.encode("utf-8"))
+
+
+def query(address, line):
+    with socket.create_connection(address) as connection:
+        connection.sendall((line + "\n").encode("utf-8"))
+        connection.shutdown(socket.SHUT_WR)
+        response = b""
+        while chunk := connection.recv(4096):
+            response += chunk
+    return response.decode("utf-8")
+
+
+def parse_address(text):
+    host, port = text.rsplit(":", 1)
+    return host, int(port)
+
+
+# transactions are applied asynchronously, so wait for a query to give the expected answer
+def wait_for(address, line, expected):
+    deadline = time.monotonic() + 10
+    while time.monotonic() < deadline:
+        response = query(address, line)
+        if response == expected:
+            return True
+        time.sleep(0.05)
+    print(f"Query {line!r} expected:\n{expected}got:\n{response}")
+    return False
+
+
+def main():
+    subprocess.check_call(["cargo", "build"])
+    success = True
+
+    with tempfile.TemporaryDirectory() as temp_directory:
+        rejections_filepath = os.path.join(temp_directory, "rejections.csv")
+        server = subprocess.Popen(
+            ["target/debug/kraken", "serve", "--listen", "127.0.0.1:0", "--query", "127.0.0.1:0",
+             "--rejections", rejections_filepath],
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+        )
+
+        # the bound addresses are printed once the server is ready
+        banner = server.stderr.readline().decode("utf-8")
+        match = re.match(r"Accepting transactions on (\S+), queries on (\S+)", banner)
+        ingest_address = parse_address(match.group(1))
+        query_address = parse_address(match.group(2))
+
+        print("Running test: serve_csv")
+        send(ingest_address, "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,4.0\nwithdrawal,1,3,40.0\n")
+        success = wait_for(query_address, "client 1",
+                           "client,available,held,total,locked\n1,6.0000,0.0000,6.0000,false\n") and success
+
+        print("Running test: serve_json")
+        send(ingest_address,
+             '{"type": "deposit", "client": 2, "tx": 4, "amount": "2.5"}\n'
+             'not json\n'
+             '{"type": "dispute", "client": 2, "tx": 4}\n')
+        success = wait_for(query_address, "client 2",
+                           "client,available,held,total,locked\n2,0.0000,2.5000,2.5000,false\n") and success
+
+        print("Running test: serve_queries")
+        success = wait_for(query_address, "all",
+                           "client,available,held,total,locked\n"
+                           "1,6.0000,0.0000,6.0000,false\n"
+                           "2,0.0000,2.5000,2.5000,false\n") and success
+        success = wait_for(query_address, "client 3", "error: unknown client 3\n") and success
+        response = query(query_address, "balance 1")
+        if not response.startswith("error: Unknown query"):
+            print(f"Expected an unknown query error, got: {response}")
+            success = False
+
+        print("Running test: serve_shutdown")
+        # rows from a connection which is still open when the shutdown arrives are applied too
+        connection = socket.create_connection(ingest_address)
+        connection.sendall("type,client,tx,amount\n".encode("utf-8")
+                           + "".join(f"deposit,3,{tx},1.0\n" for tx in range(100, 600)).encode("utf-8"))
+        success = wait_for(query_address, "shutdown", "ok\n") and success
+        output, _ = server.communicate(timeout=10)
+        connection.close()
+        expected_output = ("client,available,held,total,locked\n1,6.0000,0.0000,6.0000,false\n"
+                           "2,0.0000,2.5000,2.5000,false\n3,500.0000,0.0000,500.0000,false\n")
+        if output.decode("utf-8") != expected_output:
+            print(f"Expected final balances:\n{expected_output}got:\n{output.decode('utf-8')}")
+            success = False
+
+        # sources are named by the connection's address, which changes every run
+        with open(rejections_filepath, mode="r") as f:
+            rejections = [re.sub(r"^127\.0\.0\.1:\d+,", "<address>,", line) for line in f.read().splitlines()]
+        expected_rejections = [
+            "source,row,client,tx,type,reason",
+            "<address>,2,1,3,withdrawal,insufficient funds",
+            "<address>,1,,,,Failed to deserialize transaction: expected ident at line 1 column 2",
+        ]
+        if rejections != expected_rejections:
+            print(f"Expected rejections:\n{expected_rejections}\ngot:\n{rejections}")
+            success = False
+
+    print("Test passed" if success else "Test failed")
+
+
+if __name__ == "__main__":
+    main()